@@ -0,0 +1,89 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Expr, Fields, Index};
+
+/// Derives `McModernValue` for a struct, reading and writing its fields in declaration order.
+///
+/// A `#[packet(id = ...)]` attribute also implements `Packet` with the given packet id.
+#[proc_macro_derive(McModernValue, attributes(packet))]
+pub fn derive_mc_modern_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let helpers = quote!(::mcping::pinging::mc_modern::helpers);
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "McModernValue can only be derived for structs"));
+    };
+
+    let (read, write) = match &data.fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|v| v.ident.as_ref().unwrap()).collect::<Vec<_>>();
+            let types = fields.named.iter().map(|v| &v.ty);
+            (
+                quote!(Self { #(#names: <#types as #helpers::McModernValue>::read_from(data)?,)* }),
+                quote!(#(#helpers::McModernValue::write_to(&self.#names, target)?;)*),
+            )
+        }
+        Fields::Unnamed(fields) => {
+            let indices = (0..fields.unnamed.len()).map(Index::from);
+            let types = fields.unnamed.iter().map(|v| &v.ty);
+            (
+                quote!(Self(#(<#types as #helpers::McModernValue>::read_from(data)?,)*)),
+                quote!(#(#helpers::McModernValue::write_to(&self.#indices, target)?;)*),
+            )
+        }
+        Fields::Unit => (quote!(Self), quote!()),
+    };
+
+    let packet_id = packet_id(&input)?;
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#helpers::McModernValue));
+    }
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let packet = packet_id.map(|id| {
+        quote! {
+            impl #impl_generics #helpers::Packet for #name #type_generics #where_clause {
+                const ID: i32 = #id;
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[allow(unused_variables)] // unit structs have nothing to read or write
+        impl #impl_generics #helpers::McModernValue for #name #type_generics #where_clause {
+            fn read_from(data: &mut impl #helpers::ReadBytesExt) -> ::std::result::Result<Self, #helpers::ProtocolError> {
+                ::std::result::Result::Ok(#read)
+            }
+
+            fn write_to(&self, target: &mut impl #helpers::WriteBytesExt) -> ::std::result::Result<(), #helpers::ProtocolError> {
+                #write
+                ::std::result::Result::Ok(())
+            }
+        }
+
+        #packet
+    })
+}
+
+/// Parses the id out of a `#[packet(id = ...)]` attribute, if there is one.
+fn packet_id(input: &DeriveInput) -> syn::Result<Option<Expr>> {
+    let mut id = None;
+    for attr in input.attrs.iter().filter(|v| v.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                id = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `id`"))
+            }
+        })?;
+    }
+    Ok(id)
+}
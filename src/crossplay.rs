@@ -0,0 +1,233 @@
+use std::{collections::HashSet, io, net::SocketAddr, thread};
+
+use crate::{
+    pinging::{
+        mc_bedrock::{BedrockPingData, BedrockPingError, BedrockPinger},
+        mc_modern::{ping_json::plain_text, ModernPingData, ModernPingError, ModernPinger},
+        tcp::Timeouts,
+        Pinger,
+    },
+    resolution,
+};
+
+/// Pings a hostname over both Java and Bedrock Edition to find out which editions it serves.
+pub struct CrossplayPinger {
+    pub hostname: String,
+    pub java_port: u16,
+    pub bedrock_port: u16,
+    pub timeouts: Timeouts,
+}
+
+#[derive(Debug)]
+pub struct CrossplayData {
+    pub java: Result<(SocketAddr, ModernPingData), ModernPingError>,
+    pub bedrock: Result<(SocketAddr, BedrockPingData), BedrockPingError>,
+}
+
+impl CrossplayData {
+    /// Whether the server answered on both editions.
+    pub fn serves_both(&self) -> bool {
+        self.java.is_ok() && self.bedrock.is_ok()
+    }
+
+    /// Whether the MOTD or version strings indicate the Bedrock side is served by Geyser.
+    pub fn geyser(&self) -> bool {
+        let mentions_geyser = |v: &str| v.to_lowercase().contains("geyser");
+
+        if let Ok((_, java)) = &self.java {
+            if mentions_geyser(&java.response.version.name) {
+                return true;
+            }
+        }
+
+        let Ok((_, bedrock)) = &self.bedrock else {
+            return false;
+        };
+
+        // Geyser names its level (the sub-MOTD) "Geyser" unless configured otherwise
+        if mentions_geyser(&bedrock.motd)
+            || mentions_geyser(&bedrock.version)
+            || bedrock.sub_motd.as_deref().is_some_and(mentions_geyser)
+        {
+            return true;
+        }
+
+        // with MOTD passthrough, Geyser forwards the first line of the Java description
+        match &self.java {
+            Ok((_, java)) => {
+                let description = plain_text(&java.response.description);
+                let first_line = strip_formatting(description.lines().next().unwrap_or_default());
+                !first_line.is_empty() && first_line == strip_formatting(&bedrock.motd)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl CrossplayPinger {
+    /// Pings both editions concurrently, using the first address resolved for each.
+    pub fn ping(&self) -> CrossplayData {
+        thread::scope(|s| {
+            let java = s.spawn(|| self.ping_java());
+            let bedrock = s.spawn(|| self.ping_bedrock());
+
+            CrossplayData {
+                java: java.join().unwrap(),
+                bedrock: bedrock.join().unwrap(),
+            }
+        })
+    }
+
+    fn ping_java(&self) -> Result<(SocketAddr, ModernPingData), ModernPingError> {
+        let addr = first(resolution::resolve_minecraft_ips((self.hostname.clone(), self.java_port)))?;
+
+        let pinger = ModernPinger {
+            protocol_version: -1,
+            hostname: self.hostname.clone(),
+            timeouts: self.timeouts,
+        };
+        Ok((addr, pinger.ping(addr)?))
+    }
+
+    fn ping_bedrock(&self) -> Result<(SocketAddr, BedrockPingData), BedrockPingError> {
+        let addr = first(resolution::resolve_ips((self.hostname.clone(), self.bedrock_port)))?;
+
+        let pinger = BedrockPinger {
+            read_timeout: self.timeouts.read,
+        };
+        Ok((addr, pinger.ping(addr)?))
+    }
+}
+
+fn first(lookup: io::Result<HashSet<SocketAddr>>) -> io::Result<SocketAddr> {
+    lookup?
+        .into_iter()
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses resolved"))
+}
+
+/// Removes legacy `§` formatting codes and surrounding whitespace.
+fn strip_formatting(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    let mut chars = v.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::pinging::mc_modern::ModernPingTimings;
+
+    fn java(version: &str, description: serde_json::Value) -> Result<(SocketAddr, ModernPingData), ModernPingError> {
+        let response = serde_json::from_value(serde_json::json!({
+            "version": { "name": version, "protocol": 765 },
+            "description": description,
+        }))
+        .unwrap();
+        let timings = ModernPingTimings {
+            connect: Duration::ZERO,
+            status: Duration::ZERO,
+            response_size: 0,
+            json_parse: Duration::ZERO,
+            pong: Duration::ZERO,
+        };
+        let data = ModernPingData {
+            response,
+            latency: Duration::ZERO,
+            timings,
+        };
+        Ok(("127.0.0.1:25565".parse().unwrap(), data))
+    }
+
+    fn bedrock(motd: &str, sub_motd: Option<&str>) -> Result<(SocketAddr, BedrockPingData), BedrockPingError> {
+        let data = BedrockPingData {
+            edition: "MCPE".to_owned(),
+            motd: motd.to_owned(),
+            protocol_version: 630,
+            version: "1.20.50".to_owned(),
+            online_players: 0,
+            max_players: 20,
+            server_guid: 1,
+            sub_motd: sub_motd.map(|v| v.to_owned()),
+            game_mode: None,
+            game_mode_id: None,
+            port_v4: None,
+            port_v6: None,
+            unrecognised: vec![],
+            latency: Duration::ZERO,
+        };
+        Ok(("127.0.0.1:19132".parse().unwrap(), data))
+    }
+
+    fn java_error() -> Result<(SocketAddr, ModernPingData), ModernPingError> {
+        Err(ModernPingError::IoError(io::ErrorKind::ConnectionRefused.into()))
+    }
+
+    #[test]
+    fn geyser_sub_motd() {
+        let data = CrossplayData {
+            java: java("Paper 1.20.4", "A Minecraft Server".into()),
+            bedrock: bedrock("Another Server", Some("Geyser")),
+        };
+        assert!(data.serves_both());
+        assert!(data.geyser());
+    }
+
+    #[test]
+    fn geyser_motd_passthrough() {
+        let description = serde_json::json!({ "text": "§aA Minecraft Server\n§7second line" });
+        let data = CrossplayData {
+            java: java("Paper 1.20.4", description),
+            bedrock: bedrock("§aA Minecraft Server ", Some("world")),
+        };
+        assert!(data.geyser());
+    }
+
+    #[test]
+    fn not_geyser() {
+        let data = CrossplayData {
+            java: java("Paper 1.20.4", "A Minecraft Server".into()),
+            bedrock: bedrock("Dedicated Server", Some("Bedrock level")),
+        };
+        assert!(!data.geyser());
+
+        // a blank first line matches a blank MOTD, which says nothing about Geyser
+        let data = CrossplayData {
+            java: java("Paper 1.20.4", "§r\nsecond line".into()),
+            bedrock: bedrock("§r", Some("Bedrock level")),
+        };
+        assert!(!data.geyser());
+    }
+
+    #[test]
+    fn geyser_without_java() {
+        let data = CrossplayData {
+            java: java_error(),
+            bedrock: bedrock("A Minecraft Server", Some("Bedrock level")),
+        };
+        assert!(!data.serves_both());
+        assert!(!data.geyser());
+
+        let data = CrossplayData {
+            java: java_error(),
+            bedrock: bedrock("A Minecraft Server", Some("Geyser")),
+        };
+        assert!(data.geyser());
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(strip_formatting("§a§lBold §rserver "), "Bold server");
+        assert_eq!(strip_formatting("trailing §"), "trailing");
+        assert_eq!(strip_formatting("plain"), "plain");
+    }
+}
@@ -25,7 +25,7 @@ pub fn select_one_of<T: Display>(values: impl Iterator<Item = T>) -> std::io::Re
             // the special case
             Ok(v) if v < 0 => return Ok(Selected::Special(v)),
             // an in-bounds selection
-            Ok(v) if (v as usize) < values_vec.len() => break (v as usize),
+            Ok(v) if (v as usize) < values_vec.len() => break v as usize,
             // any other (invalid) selection
             _ => {
                 log::error!("Invalid selection: {:?}", s);
@@ -0,0 +1,7 @@
+// lets `mcping-derive` refer to this crate by name from within it
+extern crate self as mcping;
+
+pub mod crossplay;
+pub mod input;
+pub mod pinging;
+pub mod resolution;
@@ -1,5 +1,6 @@
 use std::{
     borrow::Cow,
+    net::{IpAddr, SocketAddr},
     process::ExitCode,
     time::Duration,
 };
@@ -8,15 +9,25 @@ use clap::Parser;
 use env_logger::Builder;
 use log::LevelFilter;
 
-use crate::{
+use mcping::{
+    crossplay::CrossplayPinger,
     input::selector::{self, Selected},
-    pinging::{mc_legacy::LegacyPinger, mc_modern::ModernPinger, Pinger},
+    pinging::{
+        mc_bedrock::{BedrockPingData, BedrockPinger},
+        mc_legacy::{LegacyPingData, LegacyPingError, LegacyPinger},
+        mc_modern::{
+            latency::{LatencySampler, LatencyStats},
+            login::{LoginOutcome, LoginProber},
+            ModernPingData, ModernPingTimings, ModernPinger,
+        },
+        mc_query::{QueryMode, QueryPinger, QueryResponse, RetryPolicy},
+        pool,
+        tcp::Timeouts,
+        Pinger,
+    },
+    resolution::{self, ResolutionTimings},
 };
 
-mod input;
-mod pinging;
-mod resolution;
-
 #[derive(Parser, Debug)]
 #[clap(name = "mcping")]
 struct Options {
@@ -24,17 +35,87 @@ struct Options {
     verbose: bool,
     #[arg(long)]
     query: bool,
+    /// Ping a Bedrock Edition server.
+    #[arg(long, conflicts_with = "query")]
+    bedrock: bool,
+    /// Ping both Java and Bedrock Edition and report which are served.
+    #[arg(long, conflicts_with_all = ["query", "bedrock"])]
+    crossplay: bool,
+    /// Port to send query requests to, if it differs from the game port.
+    #[arg(long, requires = "query")]
+    query_port: Option<u16>,
+    /// Local address to send query requests from.
+    #[arg(long, requires = "query")]
+    query_bind: Option<IpAddr>,
+    /// How many times each query request is sent before giving up.
+    #[arg(long, requires = "query", default_value_t = 3)]
+    query_attempts: u32,
+    /// Milliseconds to wait for a response to each query request.
+    #[arg(long, requires = "query", default_value_t = 2000, value_parser = clap::value_parser!(u64).range(1..))]
+    query_timeout: u64,
+    /// Start logging in as a throwaway player, reporting whether the server is online-mode,
+    /// offline-mode or refuses the player.
+    #[arg(long, conflicts_with_all = ["query", "bedrock", "crossplay", "count"])]
+    login: bool,
+    /// Protocol version to log in with, to see what players on that version are told. Defaults
+    /// to the version the server announces.
+    #[arg(long, requires = "login", allow_negative_numbers = true)]
+    protocol: Option<i32>,
     #[arg(long = "ping")]
     only_ping: bool,
+    /// Number of status pings to send to each address, reporting latency statistics.
+    #[arg(short, long, default_value_t = 1, conflicts_with_all = ["query", "bedrock", "crossplay"])]
+    count: u32,
+    /// Milliseconds to wait between pings when sending more than one.
+    #[arg(long, default_value_t = 0, requires = "count")]
+    interval: u64,
+    /// Maximum number of addresses pinged at once when pinging all addresses.
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+    /// Milliseconds to wait for a TCP connection to be established.
+    #[arg(long, default_value_t = 5000, value_parser = clap::value_parser!(u64).range(1..))]
+    connect_timeout: u64,
+    /// Milliseconds a single read may wait for data.
+    #[arg(long, default_value_t = 5000, value_parser = clap::value_parser!(u64).range(1..))]
+    read_timeout: u64,
+    /// Milliseconds a single write may wait to be sent.
+    #[arg(long, default_value_t = 5000, value_parser = clap::value_parser!(u64).range(1..))]
+    write_timeout: u64,
+    /// Milliseconds a whole ping may take.
+    #[arg(long, default_value_t = 15000, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: u64,
     addr: String,
 }
 
+impl Options {
+    fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            connect: Duration::from_millis(self.connect_timeout),
+            read: Duration::from_millis(self.read_timeout),
+            write: Duration::from_millis(self.write_timeout),
+            total: Duration::from_millis(self.timeout),
+        }
+    }
+}
+
 fn main() -> ExitCode {
     let options = Options::parse();
     init_logger(options.verbose);
 
     let name = {
-        let opts = options.addr.split(':').collect::<Vec<_>>();
+        let opts = if let Some(addr) = options.addr.strip_prefix('[') {
+            // bracketed IPv6 address, optionally followed by a port
+            match addr.split_once(']') {
+                Some((ip, "")) => vec![ip],
+                Some((ip, port)) if port.starts_with(':') => vec![ip, &port[1..]],
+                _ => {
+                    log::error!("Invalid address provided.");
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            options.addr.split(':').collect::<Vec<_>>()
+        };
         if opts.len() > 2 {
             log::error!("Invalid address provided.");
             return ExitCode::FAILURE;
@@ -48,13 +129,29 @@ fn main() -> ExitCode {
                 log::error!("Bad port provided.");
                 return ExitCode::FAILURE;
             }
+        } else if options.bedrock {
+            19132
         } else {
             25565
         };
         (hostname, port)
     };
 
-    let lookup = match resolution::resolve_minecraft_ips(name.clone()) {
+    if options.crossplay {
+        return crossplay(name, options.timeouts());
+    }
+
+    let mut resolution_timings = None;
+    let lookup = if options.bedrock {
+        // SRV records are only used by Java Edition
+        resolution::resolve_ips(name.clone())
+    } else {
+        resolution::resolve_minecraft_ips_timed(name.clone()).map(|(lookup, timings)| {
+            resolution_timings = Some(timings);
+            lookup
+        })
+    };
+    let lookup = match lookup {
         Ok(v) => v,
         Err(e) => {
             log::error!("IP resolution failure.");
@@ -74,108 +171,649 @@ fn main() -> ExitCode {
     };
 
 
-    let len = addresses_to_ping.len();
-    for (idx, address_to_ping) in addresses_to_ping.into_iter().enumerate() {
+    if addresses_to_ping.len() > 1 && !options.query && !options.login {
+        return ping_all(addresses_to_ping, name.0, &options);
+    }
+
+    // every address is tried, failing if any of them did
+    let mut exit_code = ExitCode::SUCCESS;
+    for address_to_ping in addresses_to_ping {
+        if options.query {
+            let mut address_to_query = address_to_ping;
+            if let Some(port) = options.query_port {
+                address_to_query.set_port(port);
+            }
+
+            let res = query(
+                address_to_query,
+                options.query_bind,
+                RetryPolicy {
+                    attempts: options.query_attempts,
+                    timeout: Duration::from_millis(options.query_timeout),
+                },
+            );
+            if res == ExitCode::FAILURE {
+                exit_code = res;
+            }
+            continue;
+        }
+
+        if options.bedrock {
+            let res = bedrock(address_to_ping, options.timeouts().read);
+            if res == ExitCode::FAILURE {
+                exit_code = res;
+            }
+            continue;
+        }
+
+        if options.login {
+            let res = login(address_to_ping, name.0.clone(), options.protocol, options.timeouts());
+            if res == ExitCode::FAILURE {
+                exit_code = res;
+            }
+            continue;
+        }
+
+        if options.count > 1 {
+            let res = sample(
+                address_to_ping,
+                name.0.clone(),
+                options.count,
+                Duration::from_millis(options.interval),
+                options.timeouts(),
+            );
+            if res == ExitCode::FAILURE {
+                exit_code = res;
+            }
+            continue;
+        }
+
         log::info!("attempting to ping {}...", address_to_ping);
 
-        let res = (|| {
-            let l = ModernPinger {
-                protocol_version: -1,
-                hostname: name.0.clone(),
-                read_timeout: Duration::from_secs(5),
-            };
-            let data = l.ping(address_to_ping);
-    
-            match data {
-                Ok(data) => {
-                    log::info!("server description:\n{}", data.response.description);
-    
-                    if let Some(mods) = data.response.mods {
-                        log::info!(
-                            "server uses the {:?} mod software and has {} installed mods.",
-                            mods.ty,
-                            mods.mod_list.len()
-                        );
-                        log::debug!("mods: {:?}", mods.mod_list);
-                    }
-    
-                    log::info!(
-                        "server version:\n   --- {:?}\n   --- protocol version {}",
-                        data.response.version.name,
-                        data.response.version.protocol
-                    );
-    
-                    if let Some(players) = data.response.players {
-                        log::info!("players:\n   --- {}/{}", players.online, players.max);
-    
-                        if !players.sample.is_empty() {
-                            log::info!("sample:");
-                            for v in players.sample {
-                                print!("   --- {}", v.name);
-                                if options.verbose {
-                                    print!(" (uuid {})", v.id);
-                                }
-                                println!();
-                            }
-                        }
-                    } else {
-                        log::info!("server is not announcing player count.");
+        let p = FallbackPinger::new(name.0.clone(), options.timeouts());
+        let res = match p.ping(address_to_ping) {
+            Ok(status) => {
+                print_status(address_to_ping, status, resolution_timings.as_ref(), options.verbose);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                log::error!("legacy ping failed. {:?}", e);
+                ExitCode::FAILURE
+            }
+        };
+
+        if res == ExitCode::FAILURE {
+            exit_code = res;
+        }
+    }
+
+    exit_code
+}
+
+fn query(address: SocketAddr, local_addr: Option<IpAddr>, retry: RetryPolicy) -> ExitCode {
+    log::info!("attempting to query {}...", address);
+
+    let q = QueryPinger {
+        local_addr,
+        handshake_retry: retry,
+        stat_retry: retry,
+        mode: QueryMode::FullOrBasic,
+    };
+
+    let data = match q.ping(address) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("query failed. {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    log::debug!(
+        "server answered after {} handshake and {} stat attempts.",
+        data.attempts.handshake,
+        data.attempts.stat
+    );
+
+    match data.response {
+        QueryResponse::Full(data) => {
+            log::info!("hostname:");
+            println!("   --- {}", data.hostname);
+            log::info!("server version:");
+            println!("   --- {}", data.version);
+            if let Some(plugins) = data.plugins {
+                log::info!("server software:");
+                println!(
+                    "   --- {} {}",
+                    plugins.server_software,
+                    get_or(plugins.server_version, "")
+                );
+
+                if !plugins.plugins.is_empty() {
+                    log::info!("plugins:");
+                    for v in plugins.plugins {
+                        println!("   --- {} {}", v.name, get_or(v.version, ""));
                     }
-    
-                    log::info!("[{}] ping: {}ms", address_to_ping, data.latency.as_millis());
                 }
-                Err(e) => {
-                    log::info!("standard ping failed. attempting legacy ping...");
-                    log::debug!("failure reason: {:?}", e);
-                    let l = LegacyPinger {
-                        protocol_version: 0,
-                        hostname: name.0.clone(),
-                    };
-    
-                    let data = match l.ping(address_to_ping) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            log::error!("legacy ping failed. {:?}", e);
-                            return ExitCode::FAILURE;
-                        }
-                    };
-    
-                    log::info!("motd:");
-                    println!("   --- {}", get_or(data.motd, "none"));
-                    log::info!("players:");
-                    println!(
-                        "   --- {}/{}",
-                        get_or(data.online_players, "none"),
-                        get_or(data.max_players, "none")
-                    );
-    
-                    if let Some(extra) = data.extra {
-                        log::info!("protocol version:");
-                        println!("   --- {}", get_or(extra.protocol_version, "none"));
-                        log::info!("server version:");
-                        println!("   --- {}", get_or(extra.server_version, "none"));
-                    }
-    
-                    if !data.unrecognised.is_empty() {
-                        log::debug!(
-                            "unrecognised fields in ping response: {:?}",
-                            data.unrecognised
-                        );
-                    }
+                log::debug!("raw plugins field: {:?}", plugins.raw);
+            }
+            log::info!("map:");
+            println!("   --- {}", data.map);
+            log::info!("players:");
+            println!("   --- {}/{}", data.num_players, data.max_players);
+
+            if !data.players.is_empty() {
+                log::info!("online:");
+                for v in data.players {
+                    println!("   --- {}", v);
                 }
             }
-            ExitCode::SUCCESS
-        })();
 
-        if idx == len {
-            return res;
+            if !data.unrecognised.is_empty() {
+                log::debug!(
+                    "unrecognised fields in query response: {:?}",
+                    data.unrecognised
+                );
+            }
+        }
+        QueryResponse::Basic(data) => {
+            log::info!("server only answered a basic stat.");
+            log::info!("hostname:");
+            println!("   --- {}", data.motd);
+            log::info!("map:");
+            println!("   --- {}", data.map);
+            log::info!("players:");
+            println!("   --- {}/{}", data.num_players, data.max_players);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn bedrock(address: SocketAddr, read_timeout: Duration) -> ExitCode {
+    log::info!("attempting to ping {}...", address);
+
+    let b = BedrockPinger { read_timeout };
+
+    let data = match b.ping(address) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("bedrock ping failed. {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    print_bedrock(address, data);
+
+    ExitCode::SUCCESS
+}
+
+fn print_bedrock(address: SocketAddr, data: BedrockPingData) {
+    log::info!("edition:");
+    println!("   --- {}", data.edition);
+    log::info!("motd:");
+    println!("   --- {}", data.motd);
+    if let Some(sub_motd) = data.sub_motd {
+        println!("   --- {}", sub_motd);
+    }
+    if let Some(game_mode) = data.game_mode {
+        log::info!("game mode:");
+        println!("   --- {}", game_mode);
+    }
+    log::info!(
+        "server version:\n   --- {:?}\n   --- protocol version {}",
+        data.version,
+        data.protocol_version
+    );
+    log::info!("players:\n   --- {}/{}", data.online_players, data.max_players);
+
+    if !data.unrecognised.is_empty() {
+        log::debug!(
+            "unrecognised fields in ping response: {:?}",
+            data.unrecognised
+        );
+    }
+
+    log::info!("[{}] ping: {}ms", address, data.latency.as_millis());
+}
+
+fn crossplay(name: (String, u16), timeouts: Timeouts) -> ExitCode {
+    log::info!("attempting to ping {} over Java and Bedrock Edition...", name.0);
+
+    let c = CrossplayPinger {
+        hostname: name.0,
+        java_port: name.1,
+        bedrock_port: 19132,
+        timeouts,
+    };
+    let data = c.ping();
+
+    match &data.java {
+        Ok((address, java)) => {
+            log::info!("java edition:");
+            println!("   --- {:?}", java.response.version.name);
+            println!("   --- [{}] ping: {}ms", address, java.latency.as_millis());
+        }
+        Err(e) => {
+            log::info!("java edition: not served.");
+            log::debug!("failure reason: {:?}", e);
+        }
+    }
+
+    match &data.bedrock {
+        Ok((address, bedrock)) => {
+            log::info!("bedrock edition:");
+            println!("   --- {:?}", bedrock.version);
+            println!("   --- [{}] ping: {}ms", address, bedrock.latency.as_millis());
+        }
+        Err(e) => {
+            log::info!("bedrock edition: not served.");
+            log::debug!("failure reason: {:?}", e);
+        }
+    }
+
+    if data.serves_both() {
+        log::info!("server is crossplay.");
+    }
+    if data.geyser() {
+        log::info!("bedrock edition appears to be served by Geyser.");
+    }
+
+    if data.java.is_err() && data.bedrock.is_err() {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn login(address: SocketAddr, hostname: String, protocol_version: Option<i32>, timeouts: Timeouts) -> ExitCode {
+    // the server refuses players on other versions, so log in with the one it announces
+    let protocol_version = protocol_version.unwrap_or_else(|| {
+        ModernPinger {
+            protocol_version: -1,
+            hostname: hostname.clone(),
+            timeouts,
+        }
+        .ping(address)
+        .map(|v| v.response.version.protocol as i32)
+        .unwrap_or_else(|e| {
+            log::info!("status ping failed, logging in without a protocol version.");
+            log::debug!("failure reason: {:?}", e);
+            -1
+        })
+    });
+
+    let p = LoginProber {
+        protocol_version,
+        hostname,
+        username: LoginProber::random_username(),
+        timeouts,
+    };
+    log::info!(
+        "attempting to log in to {} as {} with protocol version {}...",
+        address,
+        p.username,
+        p.protocol_version
+    );
+
+    let outcome = match p.ping(address) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("login failed. {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match outcome {
+        LoginOutcome::OnlineMode => {
+            log::info!("server is in online mode, players are authenticated.");
+        }
+        LoginOutcome::OfflineMode { compression_threshold } => {
+            log::info!("server is in offline mode, the player was let in.");
+            if let Some(threshold) = compression_threshold {
+                log::debug!("server compresses packets of {} bytes and over.", threshold);
+            }
+        }
+        LoginOutcome::Disconnected(reason) => {
+            if reason.not_whitelisted() {
+                log::info!("server is whitelisted, the player was refused:");
+            } else if reason.wrong_version() {
+                log::info!("server does not accept protocol version {}:", protocol_version);
+            } else {
+                log::info!("server refused the player:");
+            }
+            for line in reason.text().lines() {
+                println!("   --- {}", line);
+            }
+            log::debug!("disconnect reason: {}", reason.reason);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn sample(
+    address: SocketAddr,
+    hostname: String,
+    count: u32,
+    interval: Duration,
+    timeouts: Timeouts,
+) -> ExitCode {
+    log::info!("attempting to ping {} {} times...", address, count);
+
+    let s = LatencySampler {
+        pinger: ModernPinger {
+            protocol_version: -1,
+            hostname,
+            timeouts,
+        },
+        count,
+        interval,
+    };
+
+    let stats = match s.ping(address) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("all pings failed. {:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    print_stats(address, &stats);
+
+    ExitCode::SUCCESS
+}
+
+fn print_stats(address: SocketAddr, stats: &LatencyStats) {
+    let ms = |v: Duration| v.as_secs_f64() * 1000.0;
+
+    log::info!("[{}] ping statistics:", address);
+    println!(
+        "   --- {} sent, {} received, {:.1}% loss",
+        stats.sent,
+        stats.received(),
+        stats.loss()
+    );
+    println!(
+        "   --- min/avg/median/max = {:.2}/{:.2}/{:.2}/{:.2}ms",
+        ms(stats.min),
+        ms(stats.avg),
+        ms(stats.median),
+        ms(stats.max)
+    );
+    println!(
+        "   --- stddev {:.2}ms, jitter {:.2}ms",
+        ms(stats.std_dev),
+        ms(stats.jitter)
+    );
+}
+
+/// Pings every address concurrently, printing each server's status as results arrive and a
+/// summary sorted by latency at the end.
+fn ping_all(addresses: Vec<SocketAddr>, hostname: String, options: &Options) -> ExitCode {
+    log::info!("testing all addresses...");
+
+    let timeouts = options.timeouts();
+    let results = if options.bedrock {
+        let b = BedrockPinger {
+            read_timeout: timeouts.read,
+        };
+        ping_all_with(&b, addresses, options.jobs, |address, data| {
+            let latency = data.latency;
+            print_bedrock(address, data);
+            Some(latency)
+        })
+    } else if options.count > 1 {
+        let s = LatencySampler {
+            pinger: ModernPinger {
+                protocol_version: -1,
+                hostname,
+                timeouts,
+            },
+            count: options.count,
+            interval: Duration::from_millis(options.interval),
+        };
+        ping_all_with(&s, addresses, options.jobs, |address, stats| {
+            print_stats(address, &stats);
+            Some(stats.avg)
+        })
+    } else {
+        let p = FallbackPinger::new(hostname, timeouts);
+        ping_all_with(&p, addresses, options.jobs, |address, status| {
+            let latency = match &status {
+                Status::Modern(data) => Some(data.latency),
+                Status::Legacy(_) => None,
+            };
+            print_status(address, status, None, options.verbose);
+            latency
+        })
+    };
+
+    let width = results
+        .iter()
+        .map(|(address, _)| address.to_string().len())
+        .max()
+        .unwrap_or_default();
+
+    log::info!("summary:");
+    for (address, answer) in &results {
+        let address = address.to_string();
+        match answer {
+            Answer::Timed(latency) => println!("   --- {:<width$}  {}ms", address, latency.as_millis()),
+            Answer::Untimed => println!("   --- {:<width$}  answered legacy ping", address),
+            Answer::Failed => println!("   --- {:<width$}  failed", address),
         }
     }
 
+    match results.first() {
+        Some((address, Answer::Timed(latency))) => log::info!(
+            "best ping is {} with a time of {}ms",
+            address,
+            latency.as_millis()
+        ),
+        Some((_, Answer::Untimed)) => {}
+        _ => {
+            log::error!("no address answered.");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // as when pinging addresses one by one, fail if any of them did
+    if results.iter().any(|(_, answer)| matches!(answer, Answer::Failed)) {
+        return ExitCode::FAILURE;
+    }
     ExitCode::SUCCESS
 }
 
-fn get_or(v: Option<String>, val: &str) -> Cow<str> {
+/// How an address answered when pinging all addresses.
+enum Answer {
+    Timed(Duration),
+    /// Answered without a latency, such as a legacy ping.
+    Untimed,
+    Failed,
+}
+
+/// Pings every address, handing each answer to `report` to print and returning the latency it
+/// reports for each address, sorted with the fastest first and failures last.
+fn ping_all_with<P>(
+    pinger: &P,
+    addresses: Vec<SocketAddr>,
+    jobs: usize,
+    mut report: impl FnMut(SocketAddr, P::Data) -> Option<Duration>,
+) -> Vec<(SocketAddr, Answer)>
+where
+    P: Pinger + Sync,
+    P::Data: Send,
+    P::Error: Send + std::fmt::Debug,
+{
+    let mut results = Vec::with_capacity(addresses.len());
+
+    pool::ping_concurrently(pinger, addresses, jobs, |address, res| match res {
+        Ok(data) => {
+            let answer = match report(address, data) {
+                Some(latency) => Answer::Timed(latency),
+                None => Answer::Untimed,
+            };
+            results.push((address, answer));
+        }
+        Err(e) => {
+            log::info!("[{}] ping failed.", address);
+            log::debug!("failure reason: {:?}", e);
+            results.push((address, Answer::Failed));
+        }
+    });
+
+    results.sort_by_key(|(_, answer)| match answer {
+        Answer::Timed(latency) => (0, *latency),
+        Answer::Untimed => (1, Duration::ZERO),
+        Answer::Failed => (2, Duration::ZERO),
+    });
+    results
+}
+
+/// A Java Edition server's status, from a modern ping or the legacy ping of servers older than
+/// 1.7.
+enum Status {
+    Modern(ModernPingData),
+    Legacy(LegacyPingData),
+}
+
+/// Pings with the modern protocol, falling back to the legacy ping for servers which do not
+/// understand it.
+struct FallbackPinger {
+    modern: ModernPinger,
+    legacy: LegacyPinger,
+}
+
+impl FallbackPinger {
+    fn new(hostname: String, timeouts: Timeouts) -> Self {
+        Self {
+            modern: ModernPinger {
+                protocol_version: -1,
+                hostname: hostname.clone(),
+                timeouts,
+            },
+            legacy: LegacyPinger {
+                protocol_version: 0,
+                hostname,
+                timeouts,
+            },
+        }
+    }
+}
+
+impl Pinger for FallbackPinger {
+    type Data = Status;
+
+    type Error = LegacyPingError;
+
+    fn ping(&self, addr: SocketAddr) -> Result<Self::Data, Self::Error> {
+        match self.modern.ping(addr) {
+            Ok(data) => Ok(Status::Modern(data)),
+            Err(e) => {
+                log::info!("[{}] standard ping failed. attempting legacy ping...", addr);
+                log::debug!("failure reason: {:?}", e);
+                self.legacy.ping(addr).map(Status::Legacy)
+            }
+        }
+    }
+}
+
+fn print_status(
+    address: SocketAddr,
+    status: Status,
+    resolution: Option<&ResolutionTimings>,
+    verbose: bool,
+) {
+    let data = match status {
+        Status::Modern(data) => data,
+        Status::Legacy(data) => return print_legacy_status(address, data),
+    };
+
+    log::info!("server description:\n{}", data.response.description);
+
+    if let Some(mods) = data.response.mods {
+        log::info!(
+            "server uses the {:?} mod software and has {} installed mods.",
+            mods.ty,
+            mods.mod_list.len()
+        );
+        log::debug!("mods: {:?}", mods.mod_list);
+    }
+
+    log::info!(
+        "server version:\n   --- {:?}\n   --- protocol version {}",
+        data.response.version.name,
+        data.response.version.protocol
+    );
+
+    if let Some(players) = data.response.players {
+        log::info!("players:\n   --- {}/{}", players.online, players.max);
+
+        if !players.sample.is_empty() {
+            log::info!("sample:");
+            for v in players.sample {
+                print!("   --- {}", v.name);
+                if verbose {
+                    print!(" (uuid {})", v.id);
+                }
+                println!();
+            }
+        }
+    } else {
+        log::info!("server is not announcing player count.");
+    }
+
+    log::info!("[{}] ping: {}ms", address, data.latency.as_millis());
+
+    if verbose {
+        print_timings(resolution, &data.timings);
+    }
+}
+
+fn print_legacy_status(address: SocketAddr, data: LegacyPingData) {
+    log::info!("[{}] answered legacy ping.", address);
+    log::info!("motd:");
+    println!("   --- {}", get_or(data.motd, "none"));
+    log::info!("players:");
+    println!(
+        "   --- {}/{}",
+        get_or(data.online_players, "none"),
+        get_or(data.max_players, "none")
+    );
+
+    if let Some(extra) = data.extra {
+        log::info!("protocol version:");
+        println!("   --- {}", get_or(extra.protocol_version, "none"));
+        log::info!("server version:");
+        println!("   --- {}", get_or(extra.server_version, "none"));
+    }
+
+    if !data.unrecognised.is_empty() {
+        log::debug!(
+            "unrecognised fields in ping response: {:?}",
+            data.unrecognised
+        );
+    }
+}
+
+fn print_timings(resolution: Option<&ResolutionTimings>, ping: &ModernPingTimings) {
+    let ms = |v: Duration| v.as_secs_f64() * 1000.0;
+
+    log::info!("timings:");
+    if let Some(resolution) = resolution {
+        println!("   --- srv lookup   {:>9.2}ms", ms(resolution.srv_lookup));
+        println!("   --- ip lookup    {:>9.2}ms", ms(resolution.ip_lookup));
+    }
+    println!("   --- tcp connect  {:>9.2}ms", ms(ping.connect));
+    println!(
+        "   --- status       {:>9.2}ms ({} bytes)",
+        ms(ping.status),
+        ping.response_size
+    );
+    println!("   --- json parse   {:>9.2}ms", ms(ping.json_parse));
+    println!("   --- pong         {:>9.2}ms", ms(ping.pong));
+}
+
+fn get_or(v: Option<String>, val: &str) -> Cow<'_, str> {
     if let Some(v) = v {
         Cow::Owned(v)
     } else {
@@ -0,0 +1,267 @@
+use std::{
+    io::{self, ErrorKind, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    num::ParseIntError,
+    str::Split,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+use super::{udp::recv_until, Pinger};
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1C;
+
+/// The "offline message" magic carried by unconnected RakNet packets.
+const OFFLINE_MESSAGE_MAGIC: [u8; 16] = [
+    0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE, 0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78,
+];
+
+#[derive(Debug)]
+pub struct BedrockPingData {
+    /// `MCPE`, or `MCEE` for Education Edition.
+    pub edition: String,
+    pub motd: String,
+    pub protocol_version: u32,
+    pub version: String,
+    pub online_players: u32,
+    pub max_players: u32,
+    pub server_guid: u64,
+    pub sub_motd: Option<String>,
+    pub game_mode: Option<String>,
+    pub game_mode_id: Option<u8>,
+    pub port_v4: Option<u16>,
+    pub port_v6: Option<u16>,
+    pub unrecognised: Vec<String>,
+    pub latency: Duration,
+}
+
+impl BedrockPingData {
+    /// Parses the `;`-delimited server id string of an Unconnected Pong.
+    fn parse(server_guid: u64, server_id: &str, latency: Duration) -> Result<Self, BedrockPingError> {
+        let mut fields = server_id.split(';');
+
+        let mut required = |name: &'static str| {
+            fields
+                .next()
+                .map(|v| v.to_owned())
+                .ok_or(BedrockPingError::MissingField(name))
+        };
+
+        let edition = required("edition")?;
+        let motd = required("motd")?;
+        let protocol_version = required("protocol version")?.parse()?;
+        let version = required("version")?;
+        let online_players = required("online players")?.parse()?;
+        let max_players = required("max players")?.parse()?;
+
+        // the remaining fields were added over time and are left out by some servers
+        let optional = |v: &mut Split<char>| v.next().filter(|v| !v.is_empty()).map(|v| v.to_owned());
+
+        // the guid is also in the packet header, which is kept if this one is missing
+        let server_guid = optional(&mut fields)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(server_guid);
+        let sub_motd = optional(&mut fields);
+        let game_mode = optional(&mut fields);
+        let game_mode_id = optional(&mut fields).map(|v| v.parse()).transpose()?;
+        let port_v4 = optional(&mut fields).map(|v| v.parse()).transpose()?;
+        let port_v6 = optional(&mut fields).map(|v| v.parse()).transpose()?;
+
+        let unrecognised = fields
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_owned())
+            .collect();
+
+        Ok(Self {
+            edition,
+            motd,
+            protocol_version,
+            version,
+            online_players,
+            max_players,
+            server_guid,
+            sub_motd,
+            game_mode,
+            game_mode_id,
+            port_v4,
+            port_v6,
+            unrecognised,
+            latency,
+        })
+    }
+}
+
+pub struct BedrockPinger {
+    pub read_timeout: Duration,
+}
+
+impl Pinger for BedrockPinger {
+    type Data = BedrockPingData;
+
+    type Error = BedrockPingError;
+
+    fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let local_addr = match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        let socket = UdpSocket::bind(SocketAddr::new(local_addr, 0))?;
+        socket.connect(addr)?;
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_millis() as i64)
+            .unwrap_or_default();
+
+        let mut request = Vec::with_capacity(33);
+        request.write_u8(UNCONNECTED_PING)?;
+        request.write_i64::<BigEndian>(time)?;
+        request.extend_from_slice(&OFFLINE_MESSAGE_MAGIC);
+        request.write_i64::<BigEndian>(rand::random())?; // client guid
+
+        socket.send(&request)?;
+        let start = Instant::now();
+
+        let deadline = start + self.read_timeout;
+        let mut data = vec![0; 1500];
+        loop {
+            let Some(read) = recv_until(&socket, &mut data, deadline)? else {
+                return Err(BedrockPingError::TimeoutReached);
+            };
+            let latency = start.elapsed();
+
+            let Some((server_guid, server_id)) = decode_pong(&data[..read], time)? else {
+                continue;
+            };
+
+            return BedrockPingData::parse(server_guid, &server_id, latency);
+        }
+    }
+}
+
+/// Parses an Unconnected Pong into its server guid and server id string, or returns `None` for
+/// a stray datagram: one which is not a pong to the ping sent at `time`.
+fn decode_pong(mut packet: &[u8], time: i64) -> Result<Option<(u64, String)>, BedrockPingError> {
+    let packet_id = packet.first().copied();
+    if packet_id != Some(UNCONNECTED_PONG) {
+        log::debug!("discarding datagram with packet id {:?}", packet_id);
+        return Ok(None);
+    }
+    packet = &packet[1..];
+
+    let echoed_time = packet.read_i64::<BigEndian>()?;
+    if echoed_time != time {
+        log::debug!("discarding pong for ping sent at {}", echoed_time);
+        return Ok(None);
+    }
+
+    let server_guid = packet.read_u64::<BigEndian>()?;
+
+    let mut magic = [0; 16];
+    packet.read_exact(&mut magic)?;
+    if magic != OFFLINE_MESSAGE_MAGIC {
+        return Err(BedrockPingError::BadMagic);
+    }
+
+    let len = packet.read_u16::<BigEndian>()? as usize;
+    let server_id = packet.get(..len).ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+    Ok(Some((server_guid, String::from_utf8_lossy(server_id).into_owned())))
+}
+
+#[derive(Error, Debug)]
+pub enum BedrockPingError {
+    #[error("Timeout reached.")]
+    TimeoutReached,
+
+    #[error("Pong does not carry the offline message magic")]
+    BadMagic,
+
+    #[error("Server id string is missing the {0} field")]
+    MissingField(&'static str),
+
+    #[error("Server id string has an invalid number")]
+    BadNumber(#[from] ParseIntError),
+
+    #[error("IO error during ping")]
+    IoError(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pong(time: i64, server_id: &str) -> Vec<u8> {
+        let mut packet = vec![UNCONNECTED_PONG];
+        packet.write_i64::<BigEndian>(time).unwrap();
+        packet.write_u64::<BigEndian>(42).unwrap();
+        packet.extend_from_slice(&OFFLINE_MESSAGE_MAGIC);
+        packet.write_u16::<BigEndian>(server_id.len() as u16).unwrap();
+        packet.extend_from_slice(server_id.as_bytes());
+        packet
+    }
+
+    #[test]
+    fn dedicated_server() {
+        let server_id = "MCPE;Dedicated Server;390;1.14.60;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;";
+        let data = BedrockPingData::parse(42, server_id, Duration::ZERO).unwrap();
+
+        assert_eq!(data.edition, "MCPE");
+        assert_eq!(data.motd, "Dedicated Server");
+        assert_eq!(data.protocol_version, 390);
+        assert_eq!(data.version, "1.14.60");
+        assert_eq!((data.online_players, data.max_players), (0, 10));
+        assert_eq!(data.server_guid, 13253860892328930865);
+        assert_eq!(data.sub_motd.as_deref(), Some("Bedrock level"));
+        assert_eq!(data.game_mode.as_deref(), Some("Survival"));
+        assert_eq!(data.game_mode_id, Some(1));
+        assert_eq!((data.port_v4, data.port_v6), (Some(19132), Some(19133)));
+        assert!(data.unrecognised.is_empty());
+    }
+
+    #[test]
+    fn short_server_id() {
+        let data = BedrockPingData::parse(42, "MCPE;Nukkit Server;440;1.17.0;2;20", Duration::ZERO).unwrap();
+
+        assert_eq!(data.motd, "Nukkit Server");
+        assert_eq!((data.online_players, data.max_players), (2, 20));
+        assert_eq!(data.server_guid, 42);
+        assert_eq!(data.sub_motd, None);
+        assert_eq!(data.game_mode_id, None);
+        assert_eq!((data.port_v4, data.port_v6), (None, None));
+    }
+
+    #[test]
+    fn bad_guid_falls_back_to_header() {
+        let data = BedrockPingData::parse(42, "MCPE;motd;390;1.14.60;0;10;not-a-guid;", Duration::ZERO).unwrap();
+        assert_eq!(data.server_guid, 42);
+    }
+
+    #[test]
+    fn missing_field() {
+        assert!(matches!(
+            BedrockPingData::parse(42, "MCPE;motd;390", Duration::ZERO),
+            Err(BedrockPingError::MissingField("version"))
+        ));
+    }
+
+    #[test]
+    fn stray_datagrams() {
+        assert!(decode_pong(&[], 7).unwrap().is_none());
+        assert!(decode_pong(&[UNCONNECTED_PING, 0, 0], 7).unwrap().is_none());
+        assert!(decode_pong(&pong(6, "MCPE"), 7).unwrap().is_none());
+
+        let (guid, server_id) = decode_pong(&pong(7, "MCPE;motd"), 7).unwrap().unwrap();
+        assert_eq!((guid, server_id.as_str()), (42, "MCPE;motd"));
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut packet = pong(7, "MCPE");
+        packet[20] ^= 0xFF;
+        assert!(matches!(decode_pong(&packet, 7), Err(BedrockPingError::BadMagic)));
+    }
+}
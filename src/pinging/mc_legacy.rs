@@ -1,6 +1,5 @@
 use std::{
-    io::{self, Write},
-    net::TcpStream,
+    io::{self, Read, Write},
     str::Split,
     string::FromUtf16Error,
 };
@@ -8,7 +7,9 @@ use std::{
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use thiserror::Error;
 
-use super::Pinger;
+use super::{tcp::{TimedStream, Timeouts}, Pinger, StreamPinger};
+#[cfg(feature = "tokio")]
+use super::{tcp::AsyncTimedStream, AsyncPinger};
 #[derive(Debug)]
 
 pub struct ExtraLegacyPingData {
@@ -26,129 +27,181 @@ pub struct LegacyPingData {
 
 pub struct LegacyPinger {
     pub protocol_version: u8,
-    pub hostname: String
+    pub hostname: String,
+    pub timeouts: Timeouts
 }
 
-impl Pinger for LegacyPinger {
-    type Data = LegacyPingData;
+impl LegacyPinger {
+    fn request(&self, port: u16) -> io::Result<Vec<u8>> {
+        let mut request = vec![];
 
-    type Error = LegacyPingError;
-
-    fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
-        let mut stream = TcpStream::connect(addr)?;
-
-        stream.write_all(&[0xFE])?; // opening bytes
-        stream.write_all(&[0x01])?;
-        stream.write_all(&[0xFA])?;
+        request.write_all(&[0xFE])?; // opening bytes
+        request.write_all(&[0x01])?;
+        request.write_all(&[0xFA])?;
 
         let plugin_message_header = "MC|PingHost".encode_utf16().collect::<Vec<_>>();
-        stream.write_u16::<BigEndian>(plugin_message_header.len() as u16)?;
+        request.write_u16::<BigEndian>(plugin_message_header.len() as u16)?;
         for v in plugin_message_header {
-            stream.write_u16::<BigEndian>(v)?;
+            request.write_u16::<BigEndian>(v)?;
         }
         
         let hostname = self.hostname.encode_utf16().collect::<Vec<_>>();
-        stream.write_u16::<BigEndian>((hostname.len() as u16) + 7)?; // 7 for rest of the data
+        request.write_u16::<BigEndian>((hostname.len() as u16) + 7)?; // 7 for rest of the data
         
-        stream.write_u8(self.protocol_version)?;
+        request.write_u8(self.protocol_version)?;
 
-        stream.write_u16::<BigEndian>(hostname.len() as u16)?;
+        request.write_u16::<BigEndian>(hostname.len() as u16)?;
 
         for v in hostname {
-            stream.write_u16::<BigEndian>(v)?;
+            request.write_u16::<BigEndian>(v)?;
         }
 
-        stream.write_u32::<BigEndian>(addr.port() as u32)?;
+        request.write_u32::<BigEndian>(port as u32)?;
+
+        Ok(request)
+    }
+}
+
+/// Parses the kick message string which carries the ping response.
+fn parse_response(read_data: String) -> Result<LegacyPingData, LegacyPingError> {
+    let get_field = |v: &mut Split<char>| {
+        v.next()
+            .map(|v| v.to_owned())
+    };
+
+    let data = if read_data.starts_with("§1\0") {
+        // 1.6 ping
 
+        let mut fields = read_data.trim_start_matches("§1\0").split('\0'); // fields are delimited by NUL-chars
 
+        let protocol_version = get_field(&mut fields);
 
-        let packet_id = stream.read_u8()?;
+        let server_version = get_field(&mut fields);
 
 
-        if packet_id != 0xFF {
-            return Err(LegacyPingError::WrongId(packet_id));
+        #[allow(clippy::unnecessary_unwrap)]
+        if protocol_version.is_some() && server_version.is_none() {
+            // only one field was delivered. something weird happened!
+            return Err(LegacyPingError::UnexpectedReply(protocol_version.unwrap()))
         }
 
-        let string_length_in_chars = stream.read_u16::<BigEndian>()? as usize;
+        let motd = get_field(&mut fields);
 
-        let mut utf16_str = vec![0u16; string_length_in_chars];
+        let online_players = get_field(&mut fields);
 
-        stream.read_u16_into::<BigEndian>(&mut utf16_str)?;
+        let max_players = get_field(&mut fields);
 
-        let read_data = String::from_utf16(&utf16_str)?;
+        let unrecognised = fields.map(|v| v.to_owned()).collect::<Vec<_>>();
 
-        let get_field = |v: &mut Split<char>| {
-            v.next()
-                .map(|v| v.to_owned())
-        };
+        LegacyPingData {
+            motd,
+            online_players,
+            max_players,
+            unrecognised,
+            extra: Some(ExtraLegacyPingData {
+                server_version,
+                protocol_version,
+            }),
+        }
+    } else {
+        // pre 1.6 ping
+
+        let mut fields = read_data.split('§'); // fields are delimited by the section symbol
 
-        let data = if read_data.starts_with("§1\0") {
-            // 1.6 ping
 
-            let mut fields = read_data.trim_start_matches("§1\0").split('\0'); // fields are delimited by NUL-chars
+        let motd = get_field(&mut fields);
 
-            let protocol_version = get_field(&mut fields);
+        let online_players = get_field(&mut fields);
 
-            let server_version = get_field(&mut fields);
+        #[allow(clippy::unnecessary_unwrap)]
+        if motd.is_some() && online_players.is_none() {
+            // only one field was delivered. something weird happened!
+            return Err(LegacyPingError::UnexpectedReply(motd.unwrap()))
+        }
 
+        let max_players = get_field(&mut fields);
 
-            #[allow(clippy::unnecessary_unwrap)]
-            if protocol_version.is_some() && server_version.is_none() {
-                // only one field was delivered. something weird happened!
-                return Err(LegacyPingError::UnexpectedReply(protocol_version.unwrap()))
-            }
+        let unrecognised = fields.map(|v| v.to_owned()).collect::<Vec<_>>();
 
-            let motd = get_field(&mut fields);
+        LegacyPingData {
+            motd,
+            online_players,
+            max_players,
+            unrecognised,
+            extra: None,
+        }
+    };
 
-            let online_players = get_field(&mut fields);
+    Ok(data)
+}
 
-            let max_players = get_field(&mut fields);
+impl Pinger for LegacyPinger {
+    type Data = LegacyPingData;
 
-            let unrecognised = fields.map(|v| v.to_owned()).collect::<Vec<_>>();
+    type Error = LegacyPingError;
 
-            LegacyPingData {
-                motd,
-                online_players,
-                max_players,
-                unrecognised,
-                extra: Some(ExtraLegacyPingData {
-                    server_version,
-                    protocol_version,
-                }),
-            }
-        } else {
-            // pre 1.6 ping
+    fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let mut stream = TimedStream::connect(addr, self.timeouts)?;
+        self.ping_stream(&mut stream, addr.port())
+    }
+}
 
-            let mut fields = read_data.split('§'); // fields are delimited by the section symbol
+impl StreamPinger for LegacyPinger {
+    fn ping_stream<S: Read + Write>(&self, stream: &mut S, port: u16) -> std::result::Result<Self::Data, Self::Error> {
+        stream.write_all(&self.request(port)?)?;
 
+        let mut header = [0; 3];
+        stream.read_exact(&mut header)?;
 
-            let motd = get_field(&mut fields);
+        let mut string_data = vec![0; decode_header(header)?];
+        stream.read_exact(&mut string_data)?;
 
-            let online_players = get_field(&mut fields);
+        decode_response(&string_data)
+    }
+}
 
-            #[allow(clippy::unnecessary_unwrap)]
-            if motd.is_some() && online_players.is_none() {
-                // only one field was delivered. something weird happened!
-                return Err(LegacyPingError::UnexpectedReply(motd.unwrap()))
-            }
 
-            let max_players = get_field(&mut fields);
+#[cfg(feature = "tokio")]
+impl AsyncPinger for LegacyPinger {
+    type Data = LegacyPingData;
 
-            let unrecognised = fields.map(|v| v.to_owned()).collect::<Vec<_>>();
+    type Error = LegacyPingError;
 
-            LegacyPingData {
-                motd,
-                online_players,
-                max_players,
-                unrecognised,
-                extra: None,
-            }
-        };
+    async fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let mut stream = AsyncTimedStream::connect(addr, self.timeouts).await?;
 
-        Ok(data)
+        stream.write_all(&self.request(addr.port())?).await?;
+
+        let mut header = [0; 3];
+        stream.read_exact(&mut header).await?;
+
+        let mut string_data = vec![0; decode_header(header)?];
+        stream.read_exact(&mut string_data).await?;
+
+        decode_response(&string_data)
     }
 }
 
+/// Checks the packet id of the kick packet carrying the response, returning the length of its
+/// string in bytes.
+fn decode_header(header: [u8; 3]) -> Result<usize, LegacyPingError> {
+    let packet_id = header[0];
+    if packet_id != 0xFF {
+        return Err(LegacyPingError::WrongId(packet_id));
+    }
+
+    let string_length_in_chars = u16::from_be_bytes([header[1], header[2]]) as usize;
+    Ok(string_length_in_chars * 2)
+}
+
+/// Decodes the UTF-16 string of the kick packet and parses the response it carries.
+fn decode_response(mut string_data: &[u8]) -> Result<LegacyPingData, LegacyPingError> {
+    let mut utf16_str = vec![0u16; string_data.len() / 2];
+    string_data.read_u16_into::<BigEndian>(&mut utf16_str)?;
+
+    parse_response(String::from_utf16(&utf16_str)?)
+}
+
 #[derive(Error, Debug)]
 pub enum LegacyPingError {
     #[error("Unexpected reply from server: {0}")]
@@ -163,3 +216,80 @@ pub enum LegacyPingError {
     #[error("IO error during ping")]
     IoError(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinging::recording::Recording;
+
+    /// Pings a server answering with the kick message `response`.
+    fn ping(response: &str) -> (Result<LegacyPingData, LegacyPingError>, Vec<u8>) {
+        let response = response.encode_utf16().collect::<Vec<_>>();
+        let mut server = vec![0xFF];
+        server.write_u16::<BigEndian>(response.len() as u16).unwrap();
+        for v in response {
+            server.write_u16::<BigEndian>(v).unwrap();
+        }
+
+        let pinger = LegacyPinger {
+            protocol_version: 74,
+            hostname: "localhost".to_owned(),
+            timeouts: Timeouts::default(),
+        };
+        let mut recording = Recording::new(server);
+        (pinger.ping_stream(&mut recording, 25565), recording.client)
+    }
+
+    #[test]
+    fn ping_1_6() {
+        let (data, client) = ping(&["§1", "127", "1.20.4", "A Minecraft Server", "1", "20"].join("\0"));
+        let data = data.unwrap();
+
+        assert_eq!(data.motd.as_deref(), Some("A Minecraft Server"));
+        assert_eq!(data.online_players.as_deref(), Some("1"));
+        assert_eq!(data.max_players.as_deref(), Some("20"));
+        assert!(data.unrecognised.is_empty());
+        let extra = data.extra.unwrap();
+        assert_eq!(extra.protocol_version.as_deref(), Some("127"));
+        assert_eq!(extra.server_version.as_deref(), Some("1.20.4"));
+
+        assert!(client.starts_with(&[0xFE, 0x01, 0xFA, 0x00, 0x0B]));
+        // length of the rest, protocol version, then the hostname and port
+        assert_eq!(client[27..32], [0x00, 0x10, 74, 0x00, 0x09]);
+        assert!(client.ends_with(&[0x00, 0x00, 0x63, 0xDD]));
+    }
+
+    #[test]
+    fn ping_pre_1_6() {
+        let (data, _) = ping("A Minecraft Server§1§20");
+        let data = data.unwrap();
+
+        assert_eq!(data.motd.as_deref(), Some("A Minecraft Server"));
+        assert_eq!(data.online_players.as_deref(), Some("1"));
+        assert_eq!(data.max_players.as_deref(), Some("20"));
+        assert!(data.extra.is_none());
+    }
+
+    #[test]
+    fn single_field() {
+        let (data, _) = ping(&["§1", "127"].join("\0"));
+        assert!(matches!(data, Err(LegacyPingError::UnexpectedReply(v)) if v == "127"));
+
+        let (data, _) = ping("A Minecraft Server");
+        assert!(matches!(data, Err(LegacyPingError::UnexpectedReply(_))));
+    }
+
+    #[test]
+    fn wrong_id() {
+        let mut recording = Recording::new(vec![0x00; 3]);
+        let pinger = LegacyPinger {
+            protocol_version: 74,
+            hostname: "localhost".to_owned(),
+            timeouts: Timeouts::default(),
+        };
+        assert!(matches!(
+            pinger.ping_stream(&mut recording, 25565),
+            Err(LegacyPingError::WrongId(0x00))
+        ));
+    }
+}
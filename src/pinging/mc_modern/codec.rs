@@ -0,0 +1,233 @@
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use super::helpers::{read_frame, McModernValue, Packet, ProtocolError, VarInt, MAX_PACKET_LEN};
+#[cfg(feature = "tokio")]
+use super::helpers::frame_length;
+#[cfg(feature = "tokio")]
+use crate::pinging::tcp::AsyncTimedStream;
+
+/// Maximum length of a packet once decompressed.
+pub const MAX_DECOMPRESSED_LEN: usize = 1 << 23;
+
+/// Reads and writes length-prefixed packets over a stream, compressing them once the server has
+/// sent Set Compression.
+pub struct FramedStream<S> {
+    stream: S,
+    compression_threshold: Option<usize>,
+}
+
+impl<S> FramedStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            compression_threshold: None,
+        }
+    }
+
+    /// Switches to the compressed format, compressing packets of at least `threshold` bytes, or
+    /// back to the uncompressed one for `None`.
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Turns a frame read off the wire, without its length, into the packet's id and body.
+    fn decode(&self, frame: Vec<u8>) -> Result<Vec<u8>, ProtocolError> {
+        match self.compression_threshold {
+            Some(threshold) => decompress(&frame, threshold),
+            None => Ok(frame),
+        }
+    }
+
+    /// Turns a packet's id and body into the bytes sent for it, length included.
+    fn encode(&self, packet: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+        let frame = match self.compression_threshold {
+            Some(threshold) => compress(packet, threshold)?,
+            None => packet.to_vec(),
+        };
+
+        if frame.len() > MAX_PACKET_LEN {
+            return Err(ProtocolError::PacketTooLarge(frame.len(), MAX_PACKET_LEN));
+        }
+        let mut data = vec![];
+        VarInt(frame.len() as i32).write_to(&mut data)?;
+        data.extend_from_slice(&frame);
+        Ok(data)
+    }
+}
+
+impl<S: Read + Write> FramedStream<S> {
+    /// Reads the next packet, returning its id and body, decompressed if need be.
+    pub fn read_frame(&mut self) -> Result<Vec<u8>, ProtocolError> {
+        let frame = read_frame(&mut self.stream)?;
+        self.decode(frame)
+    }
+
+    /// Writes a packet's id and body, compressing it if need be.
+    pub fn write_frame(&mut self, packet: &[u8]) -> Result<(), ProtocolError> {
+        let data = self.encode(packet)?;
+        self.stream.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn write_packet<P: Packet>(&mut self, packet: &P) -> Result<(), ProtocolError> {
+        self.write_frame(&packet.encode()?)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl FramedStream<AsyncTimedStream> {
+    /// Async counterpart of [`FramedStream::read_frame`].
+    pub(crate) async fn read_frame(&mut self) -> Result<Vec<u8>, ProtocolError> {
+        // a VarInt is at most 5 bytes, read it a byte at a time
+        let mut length = Vec::with_capacity(5);
+        loop {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).await?;
+            length.push(byte[0]);
+            if byte[0] & 0x80 == 0 || length.len() == 5 {
+                break;
+            }
+        }
+        let length = frame_length(VarInt::read_from(&mut length.as_slice())?.0)?;
+
+        let mut frame = vec![0; length];
+        self.stream.read_exact(&mut frame).await?;
+        self.decode(frame)
+    }
+
+    /// Async counterpart of [`FramedStream::write_frame`].
+    pub(crate) async fn write_frame(&mut self, packet: &[u8]) -> Result<(), ProtocolError> {
+        let data = self.encode(packet)?;
+        self.stream.write_all(&data).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn write_packet<P: Packet>(&mut self, packet: &P) -> Result<(), ProtocolError> {
+        self.write_frame(&packet.encode()?).await
+    }
+}
+
+/// Unpacks a compressed frame, which starts with the length of the packet once decompressed, or
+/// 0 if it was sent uncompressed.
+fn decompress(mut frame: &[u8], threshold: usize) -> Result<Vec<u8>, ProtocolError> {
+    let data_length = VarInt::read_from(&mut frame)?.0;
+    let data_length = usize::try_from(data_length).map_err(|_| ProtocolError::NegativeLength(data_length))?;
+
+    if data_length == 0 {
+        return Ok(frame.to_vec());
+    }
+    if data_length < threshold {
+        return Err(ProtocolError::BelowCompressionThreshold(data_length, threshold));
+    }
+    if data_length > MAX_DECOMPRESSED_LEN {
+        return Err(ProtocolError::PacketTooLarge(data_length, MAX_DECOMPRESSED_LEN));
+    }
+
+    // read one byte past the expected length to catch packets which decompress to more
+    let mut packet = Vec::with_capacity(data_length);
+    ZlibDecoder::new(frame)
+        .take(data_length as u64 + 1)
+        .read_to_end(&mut packet)?;
+
+    if packet.len() != data_length {
+        return Err(ProtocolError::DataLengthMismatch(packet.len(), data_length));
+    }
+    Ok(packet)
+}
+
+fn compress(packet: &[u8], threshold: usize) -> Result<Vec<u8>, ProtocolError> {
+    let mut frame = vec![];
+
+    if packet.len() < threshold {
+        VarInt(0).write_to(&mut frame)?;
+        frame.extend_from_slice(packet);
+        return Ok(frame);
+    }
+
+    VarInt(packet.len() as i32).write_to(&mut frame)?;
+    let mut encoder = ZlibEncoder::new(frame, Compression::default());
+    encoder.write_all(packet)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Writes `packet` with the given threshold, returning the bytes sent and the packet read back.
+    fn round_trip(packet: &[u8], threshold: Option<usize>) -> (Vec<u8>, Vec<u8>) {
+        let mut framed = FramedStream::new(Cursor::new(vec![]));
+        framed.set_compression(threshold);
+        framed.write_frame(packet).unwrap();
+
+        let sent = framed.get_ref().get_ref().clone();
+        framed.get_mut().set_position(0);
+        (sent, framed.read_frame().unwrap())
+    }
+
+    #[test]
+    fn uncompressed() {
+        let (sent, read) = round_trip(&[0x00, 1, 2, 3], None);
+        assert_eq!(sent, [4, 0x00, 1, 2, 3]);
+        assert_eq!(read, [0x00, 1, 2, 3]);
+    }
+
+    #[test]
+    fn below_threshold() {
+        let (sent, read) = round_trip(&[0x00, 1, 2, 3], Some(256));
+        assert_eq!(sent, [5, 0, 0x00, 1, 2, 3]);
+        assert_eq!(read, [0x00, 1, 2, 3]);
+    }
+
+    #[test]
+    fn compressed() {
+        let packet = [0x01; 1000];
+        let (sent, read) = round_trip(&packet, Some(256));
+        assert!(sent.len() < 100);
+        assert_eq!(&sent[1..3], [0xe8, 0x07]); // data length of 1000
+        assert_eq!(read, packet);
+    }
+
+    #[test]
+    fn wrong_data_length() {
+        let mut frame = vec![];
+        VarInt(999).write_to(&mut frame).unwrap();
+        let mut encoder = ZlibEncoder::new(frame, Compression::default());
+        encoder.write_all(&[0x01; 1000]).unwrap();
+        let frame = encoder.finish().unwrap();
+
+        assert!(matches!(decompress(&frame, 256), Err(ProtocolError::DataLengthMismatch(1000, 999))));
+        assert!(matches!(decompress(&frame, 1024), Err(ProtocolError::BelowCompressionThreshold(999, 1024))));
+    }
+
+    #[test]
+    fn oversize_data_length() {
+        let mut frame = vec![];
+        VarInt(i32::MAX).write_to(&mut frame).unwrap();
+        assert!(matches!(decompress(&frame, 256), Err(ProtocolError::PacketTooLarge(..))));
+
+        let mut frame = vec![];
+        VarInt(-1).write_to(&mut frame).unwrap();
+        assert!(matches!(decompress(&frame, 256), Err(ProtocolError::NegativeLength(-1))));
+    }
+}
@@ -1,8 +1,16 @@
 use std::{io, ops::Deref};
 
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::BigEndian;
 use thiserror::Error;
 
+pub use byteorder::{ReadBytesExt, WriteBytesExt};
+pub use mcping_derive::McModernValue;
+
+/// Maximum length of a protocol string, in characters.
+pub const MAX_STRING_LEN: usize = 32767;
+
+/// Maximum length of a packet, the largest value of a 3 byte VarInt.
+pub const MAX_PACKET_LEN: usize = (1 << 21) - 1;
 
 pub trait McModernValue: Sized {
     fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError>;
@@ -10,8 +18,33 @@ pub trait McModernValue: Sized {
     fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError>;
 }
 
+/// A packet, sent prefixed with its length and id.
+pub trait Packet: McModernValue {
+    const ID: i32;
+
+    /// The packet's id followed by the packet.
+    fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let mut packet = vec![];
+        VarInt(Self::ID).write_to(&mut packet)?;
+        self.write_to(&mut packet)?;
+        Ok(packet)
+    }
+
+    /// Writes the packet's length and id followed by the packet, uncompressed.
+    fn write_frame(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        let packet = self.encode()?;
+
+        if packet.len() > MAX_PACKET_LEN {
+            return Err(ProtocolError::PacketTooLarge(packet.len(), MAX_PACKET_LEN));
+        }
+        VarInt(packet.len() as i32).write_to(target)?;
+        target.write_all(&packet)?;
+        Ok(())
+    }
+}
+
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct VarInt(pub i32);
 
 impl VarInt {
@@ -60,15 +93,21 @@ impl McModernValue for VarInt {
 
 impl McModernValue for String {
     fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
-        let len = VarInt::read_from(data)?;
-        let mut string_data = vec![0; len.0 as usize];
+        // a character takes up to 3 bytes in the protocol's modified UTF-8
+        let len = checked_length(VarInt::read_from(data)?.0, MAX_STRING_LEN * 3, |len| {
+            ProtocolError::StringTooLong(len, MAX_STRING_LEN * 3)
+        })?;
+        let mut string_data = vec![0; len];
         data.read_exact(&mut string_data)?;
 
-        Ok(String::from_utf8_lossy(&string_data).to_string())
+        let string = String::from_utf8_lossy(&string_data).to_string();
+        check_string_length(&string)?;
+        Ok(string)
 
     }
 
     fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        check_string_length(self)?;
         let string_data = self.as_bytes();
 
         VarInt(string_data.len() as i32).write_to(target)?;
@@ -77,10 +116,509 @@ impl McModernValue for String {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VarLong(pub i64);
+
+impl VarLong {
+    const SEGMENT_BITS: i64 = 0x7F;
+    const CONTINUE_BIT: i64 = 0x80;
+    const MAX_LEN: i64 = 64;
+}
+impl Deref for VarLong {
+    type Target = i64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl McModernValue for VarLong {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        let mut value = 0;
+        let mut position = 0;
+        loop {
+            let current_byte = data.read_u8()? as i64;
+            value |= (current_byte & Self::SEGMENT_BITS) << position;
+            if (current_byte & Self::CONTINUE_BIT) == 0 {
+                return Ok(Self(value));
+            }
+            position += 7;
+            if position >= Self::MAX_LEN {
+                return Err(ProtocolError::VarLongTooLarge);
+            }
+        }
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        let mut value = self.0;
+        loop {
+            if (value & !Self::SEGMENT_BITS) == 0 {
+                target.write_u8(value as u8)?;
+                return Ok(());
+            }
+
+            target.write_u8(((value & Self::SEGMENT_BITS) | Self::CONTINUE_BIT) as u8)?;
+
+            value = ((value as u64) >> 7) as i64;
+        }
+    }
+}
+
+impl McModernValue for bool {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        match data.read_u8()? {
+            0x00 => Ok(false),
+            0x01 => Ok(true),
+            v => Err(ProtocolError::InvalidBool(v)),
+        }
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        target.write_u8(*self as u8)?;
+        Ok(())
+    }
+}
+
+impl McModernValue for u8 {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        Ok(data.read_u8()?)
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        target.write_u8(*self)?;
+        Ok(())
+    }
+}
+
+impl McModernValue for i8 {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        Ok(data.read_i8()?)
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        target.write_i8(*self)?;
+        Ok(())
+    }
+}
+
+/// Implements [`McModernValue`] for fixed size big endian numbers.
+macro_rules! impl_big_endian {
+    ($($ty:ty => $read:ident, $write:ident;)*) => {
+        $(
+            impl McModernValue for $ty {
+                fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+                    Ok(data.$read::<BigEndian>()?)
+                }
+
+                fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+                    target.$write::<BigEndian>(*self)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_big_endian! {
+    i16 => read_i16, write_i16;
+    u16 => read_u16, write_u16;
+    i32 => read_i32, write_i32;
+    i64 => read_i64, write_i64;
+    f32 => read_f32, write_f32;
+    f64 => read_f64, write_f64;
+}
+
+/// A UUID, sent as a 128 bit big endian number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uuid(pub u128);
+
+impl McModernValue for Uuid {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        Ok(Self(data.read_u128::<BigEndian>()?))
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        target.write_u128::<BigEndian>(self.0)?;
+        Ok(())
+    }
+}
+
+/// A block position, packed into 64 bits as 26 bits of x, 26 of z and 12 of y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl McModernValue for Position {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        let value = data.read_i64::<BigEndian>()?;
+
+        // arithmetic shifts sign extend each field
+        Ok(Self {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        })
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        let value = ((self.x as i64 & 0x3FFFFFF) << 38)
+            | ((self.z as i64 & 0x3FFFFFF) << 12)
+            | (self.y as i64 & 0xFFF);
+        target.write_i64::<BigEndian>(value)?;
+        Ok(())
+    }
+}
+
+/// A namespaced identifier such as `minecraft:stone`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier(pub String);
+
+impl Identifier {
+    const DEFAULT_NAMESPACE: &'static str = "minecraft";
+
+    /// The namespace, `minecraft` if none is given.
+    pub fn namespace(&self) -> &str {
+        self.0
+            .split_once(':')
+            .map_or(Self::DEFAULT_NAMESPACE, |(namespace, _)| namespace)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.split_once(':').map_or(&self.0, |(_, path)| path)
+    }
+
+    fn is_valid(&self) -> bool {
+        let valid = |v: &str, extra: &[char]| {
+            v.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_') || extra.contains(&c))
+        };
+        valid(self.namespace(), &[]) && valid(self.path(), &['/'])
+    }
+}
+
+impl McModernValue for Identifier {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        let identifier = Self(String::read_from(data)?);
+        if !identifier.is_valid() {
+            return Err(ProtocolError::InvalidIdentifier(identifier.0));
+        }
+        Ok(identifier)
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        self.0.write_to(target)
+    }
+}
+
+/// A byte array prefixed with its length, read in one go rather than a byte at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteArray(pub Vec<u8>);
+
+impl McModernValue for ByteArray {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        let len = frame_length(VarInt::read_from(data)?.0)?;
+        let mut bytes = vec![0; len];
+        data.read_exact(&mut bytes)?;
+        Ok(Self(bytes))
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        VarInt(self.0.len() as i32).write_to(target)?;
+        target.write_all(&self.0)?;
+        Ok(())
+    }
+}
+
+/// An optional value, prefixed with a bool saying whether it is present.
+impl<T: McModernValue> McModernValue for Option<T> {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        if bool::read_from(data)? {
+            Ok(Some(T::read_from(data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        self.is_some().write_to(target)?;
+        if let Some(value) = self {
+            value.write_to(target)?;
+        }
+        Ok(())
+    }
+}
+
+/// An array prefixed with its length.
+impl<T: McModernValue> McModernValue for Vec<T> {
+    fn read_from(data: &mut impl ReadBytesExt) -> Result<Self, ProtocolError> {
+        // every element takes at least a byte, so no array is longer than a packet
+        let len = frame_length(VarInt::read_from(data)?.0)?;
+
+        // not preallocated, so a bogus length runs out of data rather than memory
+        (0..len).map(|_| T::read_from(data)).collect()
+    }
+
+    fn write_to(&self, target: &mut impl WriteBytesExt) -> Result<(), ProtocolError> {
+        VarInt(self.len() as i32).write_to(target)?;
+        for value in self {
+            value.write_to(target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a length-prefixed packet, returning it without its length.
+pub fn read_frame(data: &mut impl ReadBytesExt) -> Result<Vec<u8>, ProtocolError> {
+    let len = frame_length(VarInt::read_from(data)?.0)?;
+    let mut packet = vec![0; len];
+    data.read_exact(&mut packet)?;
+    Ok(packet)
+}
+
+/// Checks a packet length read from the wire against [`MAX_PACKET_LEN`].
+pub fn frame_length(len: i32) -> Result<usize, ProtocolError> {
+    checked_length(len, MAX_PACKET_LEN, |len| ProtocolError::PacketTooLarge(len, MAX_PACKET_LEN))
+}
+
+/// Converts a length read from the wire, failing with `too_long` if it is over `max`.
+fn checked_length(
+    len: i32,
+    max: usize,
+    too_long: impl FnOnce(usize) -> ProtocolError,
+) -> Result<usize, ProtocolError> {
+    let len = usize::try_from(len).map_err(|_| ProtocolError::NegativeLength(len))?;
+    if len > max {
+        return Err(too_long(len));
+    }
+    Ok(len)
+}
+
+fn check_string_length(string: &str) -> Result<(), ProtocolError> {
+    // the limit is counted in UTF-16 code units, as Java counts string lengths
+    let len = string.encode_utf16().count();
+    if len > MAX_STRING_LEN {
+        return Err(ProtocolError::StringTooLong(len, MAX_STRING_LEN));
+    }
+    Ok(())
+}
+
 #[derive(Error, Debug)]
 pub enum ProtocolError {
     #[error("VarInt too large!")]
     VarIntTooLarge,
+    #[error("VarLong too large!")]
+    VarLongTooLarge,
+    #[error("invalid bool value {0}")]
+    InvalidBool(u8),
+    #[error("invalid identifier {0:?}")]
+    InvalidIdentifier(String),
+    #[error("received negative length {0}")]
+    NegativeLength(i32),
+    #[error("string of length {0} is over the maximum of {1}")]
+    StringTooLong(usize, usize),
+    #[error("packet of length {0} is over the maximum of {1}")]
+    PacketTooLarge(usize, usize),
+    #[error("compressed packet of length {0} is below the compression threshold of {1}")]
+    BelowCompressionThreshold(usize, usize),
+    #[error("packet decompressed to length {0}, expected {1}")]
+    DataLengthMismatch(usize, usize),
     #[error("IO error")]
     IoError(#[from] io::Error)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use super::*;
+
+    /// Writes `value`, checking it reads back the same and uses up all the written bytes.
+    fn round_trip<T: McModernValue + PartialEq + Debug>(value: T) {
+        let mut data = vec![];
+        value.write_to(&mut data).unwrap();
+
+        let mut reader = data.as_slice();
+        assert_eq!(T::read_from(&mut reader).unwrap(), value);
+        assert!(reader.is_empty(), "{:?} left {} bytes unread", value, reader.len());
+    }
+
+    #[test]
+    fn var_int() {
+        for value in [0, 1, 127, 128, 255, 25565, 2097151, i32::MAX, -1, i32::MIN] {
+            round_trip(VarInt(value));
+        }
+
+        let mut data = vec![];
+        VarInt(-1).write_to(&mut data).unwrap();
+        assert_eq!(data, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn var_int_too_large() {
+        let data = [0xff; 6];
+        assert!(matches!(VarInt::read_from(&mut &data[..]), Err(ProtocolError::VarIntTooLarge)));
+    }
+
+    #[test]
+    fn var_long() {
+        for value in [0, 1, 127, 128, 2147483647, i64::MAX, -1, -2147483648, i64::MIN] {
+            round_trip(VarLong(value));
+        }
+
+        let mut data = vec![];
+        VarLong(-1).write_to(&mut data).unwrap();
+        assert_eq!(data, [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+
+        let data = [0xff; 11];
+        assert!(matches!(VarLong::read_from(&mut &data[..]), Err(ProtocolError::VarLongTooLarge)));
+    }
+
+    #[test]
+    fn bool() {
+        round_trip(true);
+        round_trip(false);
+        assert!(matches!(bool::read_from(&mut &[0x02][..]), Err(ProtocolError::InvalidBool(2))));
+    }
+
+    #[test]
+    fn numbers() {
+        for value in [u8::MIN, 1, u8::MAX] {
+            round_trip(value);
+        }
+        for value in [i8::MIN, -1, 0, i8::MAX] {
+            round_trip(value);
+        }
+        for value in [u16::MIN, 25565, u16::MAX] {
+            round_trip(value);
+        }
+        for value in [i16::MIN, -1, 0, i16::MAX] {
+            round_trip(value);
+        }
+        for value in [i32::MIN, -1, 0, i32::MAX] {
+            round_trip(value);
+        }
+        for value in [i64::MIN, -1, 0, i64::MAX] {
+            round_trip(value);
+        }
+        for value in [f32::MIN, -0.5, 0.0, f32::MAX, f32::INFINITY] {
+            round_trip(value);
+        }
+        for value in [f64::MIN, -0.5, 0.0, f64::MAX, f64::NEG_INFINITY] {
+            round_trip(value);
+        }
+
+        let mut data = vec![];
+        0x0102_i16.write_to(&mut data).unwrap();
+        assert_eq!(data, [0x01, 0x02]);
+    }
+
+    #[test]
+    fn uuid() {
+        round_trip(Uuid(0));
+        round_trip(Uuid(0x069a79f4_44e9_4726_a5be_fca90e38aaf5));
+        round_trip(Uuid(u128::MAX));
+    }
+
+    #[test]
+    fn position() {
+        round_trip(Position { x: 0, y: 0, z: 0 });
+        round_trip(Position { x: 18357644, y: 831, z: -20882616 });
+        round_trip(Position { x: -33554432, y: -2048, z: 33554431 });
+        round_trip(Position { x: -1, y: -1, z: -1 });
+
+        let data = 0x4607632c15b4833f_i64.to_be_bytes();
+        let position = Position::read_from(&mut &data[..]).unwrap();
+        assert_eq!(position, Position { x: 18357644, y: 831, z: -20882616 });
+    }
+
+    #[test]
+    fn string() {
+        round_trip(String::new());
+        round_trip("mcping".to_owned());
+        round_trip("§aunicode ✓".to_owned());
+        round_trip("a".repeat(MAX_STRING_LEN));
+
+        assert!(matches!("a".repeat(MAX_STRING_LEN + 1).write_to(&mut vec![]), Err(ProtocolError::StringTooLong(..))));
+    }
+
+    #[test]
+    fn string_length_prefix() {
+        let data = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(matches!(String::read_from(&mut &data[..]), Err(ProtocolError::NegativeLength(-1))));
+
+        // 2097152 bytes, rejected before reading any of them
+        let data = [0x80, 0x80, 0x80, 0x01];
+        assert!(matches!(
+            String::read_from(&mut &data[..]),
+            Err(ProtocolError::StringTooLong(2097152, len)) if len == MAX_STRING_LEN * 3
+        ));
+
+        // within the byte limit, but over the character limit
+        let mut data = vec![];
+        VarInt(MAX_STRING_LEN as i32 + 1).write_to(&mut data).unwrap();
+        data.extend(std::iter::repeat_n(b'a', MAX_STRING_LEN + 1));
+        assert!(matches!(
+            String::read_from(&mut data.as_slice()),
+            Err(ProtocolError::StringTooLong(len, MAX_STRING_LEN)) if len == MAX_STRING_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn frame_length_prefix() {
+        let data = [0x03, 0x00, 0x01, 0x02];
+        assert_eq!(read_frame(&mut &data[..]).unwrap(), [0x00, 0x01, 0x02]);
+
+        let data = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(matches!(read_frame(&mut &data[..]), Err(ProtocolError::NegativeLength(-1))));
+
+        let data = [0x80, 0x80, 0x80, 0x01];
+        assert!(matches!(
+            read_frame(&mut &data[..]),
+            Err(ProtocolError::PacketTooLarge(2097152, MAX_PACKET_LEN))
+        ));
+    }
+
+    #[test]
+    fn identifier() {
+        round_trip(Identifier("minecraft:stone".to_owned()));
+        round_trip(Identifier("mod_id:path/to.thing-1".to_owned()));
+
+        let identifier = Identifier("stone".to_owned());
+        assert_eq!(identifier.namespace(), "minecraft");
+        assert_eq!(identifier.path(), "stone");
+
+        let mut data = vec![];
+        "Bad:Name".to_owned().write_to(&mut data).unwrap();
+        assert!(matches!(Identifier::read_from(&mut data.as_slice()), Err(ProtocolError::InvalidIdentifier(_))));
+    }
+
+    #[test]
+    fn byte_array() {
+        round_trip(ByteArray(vec![]));
+        round_trip(ByteArray(vec![0, 1, 2, 255]));
+
+        let data = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(matches!(ByteArray::read_from(&mut &data[..]), Err(ProtocolError::NegativeLength(-1))));
+    }
+
+    #[test]
+    fn option() {
+        round_trip(None::<i32>);
+        round_trip(Some(5_i32));
+        round_trip(Some("mcping".to_owned()));
+        round_trip(Some(None::<VarInt>));
+    }
+
+    #[test]
+    fn array() {
+        round_trip(Vec::<VarInt>::new());
+        round_trip(vec![VarInt(1), VarInt(-1), VarInt(300)]);
+        round_trip(vec!["a".to_owned(), "b".to_owned()]);
+        round_trip(vec![Some(Uuid(1)), None]);
+
+        // claims more elements than there is data for
+        let data = [0x7f, 0x01];
+        assert!(matches!(Vec::<u8>::read_from(&mut &data[..]), Err(ProtocolError::IoError(_))));
+    }
+}
@@ -0,0 +1,170 @@
+use std::{net::SocketAddr, thread, time::Duration};
+
+use super::{ModernPingError, ModernPinger};
+use crate::pinging::Pinger;
+
+/// Latency statistics over several pings, similar to the summary printed by ICMP ping.
+#[derive(Debug)]
+pub struct LatencyStats {
+    pub sent: u32,
+    /// Latency of each answered ping, in the order they were sent.
+    pub samples: Vec<Duration>,
+    pub min: Duration,
+    pub avg: Duration,
+    pub median: Duration,
+    pub max: Duration,
+    pub std_dev: Duration,
+    /// Mean difference between the latencies of consecutive pings.
+    pub jitter: Duration,
+}
+
+impl LatencyStats {
+    /// Computes the statistics, returning `None` if no ping was answered.
+    pub fn from_samples(sent: u32, samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let secs = samples.iter().map(|v| v.as_secs_f64()).collect::<Vec<_>>();
+        let n = secs.len() as f64;
+
+        let mut sorted = secs.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        let avg = secs.iter().sum::<f64>() / n;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+        } else {
+            sorted[sorted.len() / 2]
+        };
+        let variance = secs.iter().map(|v| (v - avg).powi(2)).sum::<f64>() / n;
+        let jitter = if secs.len() > 1 {
+            secs.windows(2).map(|v| (v[1] - v[0]).abs()).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            sent,
+            min: Duration::from_secs_f64(sorted[0]),
+            avg: Duration::from_secs_f64(avg),
+            median: Duration::from_secs_f64(median),
+            max: Duration::from_secs_f64(sorted[sorted.len() - 1]),
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+            jitter: Duration::from_secs_f64(jitter),
+            samples,
+        })
+    }
+
+    pub fn received(&self) -> u32 {
+        self.samples.len() as u32
+    }
+
+    /// Percentage of pings which went unanswered.
+    pub fn loss(&self) -> f64 {
+        (self.sent - self.received()) as f64 / self.sent as f64 * 100.0
+    }
+}
+
+/// Performs several status pings, each over a new connection, and reports their latency.
+pub struct LatencySampler {
+    pub pinger: ModernPinger,
+    pub count: u32,
+    /// Delay between the end of one ping and the start of the next.
+    pub interval: Duration,
+}
+
+impl Pinger for LatencySampler {
+    type Data = LatencyStats;
+
+    /// The error of the last ping, returned only if no ping was answered.
+    type Error = ModernPingError;
+
+    fn ping(&self, addr: SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let count = self.count.max(1);
+        let mut samples = Vec::with_capacity(count as usize);
+        let mut last_error = None;
+
+        for i in 0..count {
+            if i > 0 && !self.interval.is_zero() {
+                thread::sleep(self.interval);
+            }
+
+            match self.pinger.ping(addr) {
+                Ok(data) => samples.push(data.latency),
+                Err(e) => {
+                    log::debug!("ping {} to {} failed: {:?}", i + 1, addr, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match LatencyStats::from_samples(count, samples) {
+            Some(v) => Ok(v),
+            None => Err(last_error.expect("no pings were sent")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(v: &[u64]) -> Vec<Duration> {
+        v.iter().map(|&v| Duration::from_millis(v)).collect()
+    }
+
+    /// Statistics go through `f64`, so allow for rounding.
+    fn assert_close(actual: Duration, expected_ms: f64) {
+        let actual_ms = actual.as_secs_f64() * 1000.0;
+        assert!((actual_ms - expected_ms).abs() < 1e-3, "{}ms, expected {}ms", actual_ms, expected_ms);
+    }
+
+    #[test]
+    fn even_count() {
+        let stats = LatencyStats::from_samples(4, ms(&[10, 30, 20, 40])).unwrap();
+
+        assert_close(stats.min, 10.0);
+        assert_close(stats.max, 40.0);
+        assert_close(stats.avg, 25.0);
+        assert_close(stats.median, 25.0);
+        assert_close(stats.std_dev, 125f64.sqrt());
+        assert_close(stats.jitter, 50.0 / 3.0);
+        assert_eq!(stats.samples, ms(&[10, 30, 20, 40]));
+    }
+
+    #[test]
+    fn odd_count() {
+        let stats = LatencyStats::from_samples(3, ms(&[10, 30, 20])).unwrap();
+
+        assert_close(stats.median, 20.0);
+        assert_close(stats.avg, 20.0);
+        assert_close(stats.jitter, 15.0);
+    }
+
+    #[test]
+    fn single_sample() {
+        let stats = LatencyStats::from_samples(1, ms(&[15])).unwrap();
+
+        assert_close(stats.min, 15.0);
+        assert_close(stats.median, 15.0);
+        assert_close(stats.max, 15.0);
+        assert_eq!(stats.std_dev, Duration::ZERO);
+        assert_eq!(stats.jitter, Duration::ZERO);
+    }
+
+    #[test]
+    fn loss() {
+        let stats = LatencyStats::from_samples(5, ms(&[10, 20, 30])).unwrap();
+        assert_eq!(stats.received(), 3);
+        assert_eq!(stats.loss(), 40.0);
+
+        let stats = LatencyStats::from_samples(3, ms(&[10, 20, 30])).unwrap();
+        assert_eq!(stats.loss(), 0.0);
+    }
+
+    #[test]
+    fn no_samples() {
+        assert!(LatencyStats::from_samples(3, vec![]).is_none());
+    }
+}
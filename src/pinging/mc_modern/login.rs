@@ -0,0 +1,252 @@
+use std::{
+    io::{Read, Write},
+    net::Shutdown,
+};
+
+use super::{
+    codec::FramedStream,
+    helpers::{McModernValue, Packet, ProtocolError, Uuid, VarInt},
+    packets::{
+        EncryptionRequest, Handshake, LoginDisconnect, LoginPluginRequest, LoginPluginResponse,
+        LoginStart, LoginSuccess, SetCompression, NEXT_STATE_LOGIN,
+    },
+    ping_json::plain_text,
+    read_packet, ModernPingError,
+};
+use crate::pinging::{tcp::{TimedStream, Timeouts}, Pinger, StreamPinger};
+
+/// How a server answered a login attempt.
+#[derive(Debug)]
+pub enum LoginOutcome {
+    /// The server asked to encrypt the connection, so it authenticates players with Mojang.
+    OnlineMode,
+    /// The server let an unauthenticated player in.
+    OfflineMode {
+        compression_threshold: Option<usize>,
+    },
+    /// The server refused the player, for example for not being whitelisted or running another
+    /// version.
+    Disconnected(LoginDisconnectReason),
+}
+
+/// The reason a server gave for refusing a login.
+#[derive(Debug)]
+pub struct LoginDisconnectReason {
+    /// The reason as a chat component.
+    pub reason: serde_json::Value,
+}
+
+impl LoginDisconnectReason {
+    /// Parses the reason, keeping it as plain text if it is not a JSON chat component.
+    fn parse(raw: String) -> Self {
+        let reason = serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw));
+        Self { reason }
+    }
+
+    /// The reason's plain text, with any legacy `§` formatting codes left in.
+    pub fn text(&self) -> String {
+        plain_text(&self.reason)
+    }
+
+    /// The translation key of a vanilla message, such as
+    /// `multiplayer.disconnect.not_whitelisted`.
+    pub fn translation_key(&self) -> Option<&str> {
+        self.reason.get("translate")?.as_str()
+    }
+
+    pub fn not_whitelisted(&self) -> bool {
+        self.translation_key() == Some("multiplayer.disconnect.not_whitelisted")
+    }
+
+    /// Whether the server refused the player for running another version.
+    pub fn wrong_version(&self) -> bool {
+        matches!(
+            self.translation_key(),
+            Some("multiplayer.disconnect.outdated_client" | "multiplayer.disconnect.outdated_server")
+        )
+    }
+}
+
+/// Starts logging in as a throwaway player to see whether the server lets them in, hanging up
+/// once it is known.
+pub struct LoginProber {
+    /// Must match the server's protocol version, or the server refuses the player as outdated.
+    pub protocol_version: i32,
+    pub hostname: String,
+    /// Name of the throwaway player.
+    pub username: String,
+    pub timeouts: Timeouts,
+}
+
+impl LoginProber {
+    /// A random name of the kind a real player could have, at most 16 characters long.
+    pub fn random_username() -> String {
+        format!("mcping_{:06}", rand::random::<u32>() % 1_000_000)
+    }
+
+    /// Login Start, whose layout changed several times over 1.19 and 1.20.
+    fn login_start(&self) -> Result<Vec<u8>, ProtocolError> {
+        let player_uuid = Uuid(rand::random());
+
+        if self.protocol_version >= 764 {
+            return LoginStart {
+                name: self.username.clone(),
+                player_uuid,
+            }
+            .encode();
+        }
+
+        let mut packet = vec![];
+        VarInt(LoginStart::ID).write_to(&mut packet)?;
+        self.username.write_to(&mut packet)?;
+        match self.protocol_version {
+            // 1.19 and 1.19.2 carry an optional signature, left out here
+            759 => false.write_to(&mut packet)?,
+            760 => {
+                false.write_to(&mut packet)?;
+                Some(player_uuid).write_to(&mut packet)?;
+            }
+            761..=763 => Some(player_uuid).write_to(&mut packet)?,
+            _ => {}
+        }
+        Ok(packet)
+    }
+}
+
+impl Pinger for LoginProber {
+    type Data = LoginOutcome;
+
+    type Error = ModernPingError;
+
+    fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let mut stream = TimedStream::connect(addr, self.timeouts)?;
+        let outcome = self.ping_stream(&mut stream, addr.port());
+
+        // the server may already have hung up, in which case there is nothing to shut down
+        let _ = stream.get_ref().shutdown(Shutdown::Both);
+        outcome
+    }
+}
+
+impl StreamPinger for LoginProber {
+    fn ping_stream<S: Read + Write>(&self, stream: &mut S, port: u16) -> std::result::Result<Self::Data, Self::Error> {
+        let mut stream = FramedStream::new(stream);
+
+        stream.write_packet(&Handshake {
+            protocol_version: VarInt(self.protocol_version),
+            server_address: self.hostname.clone(),
+            server_port: port,
+            next_state: VarInt(NEXT_STATE_LOGIN),
+        })?;
+        stream.write_frame(&self.login_start()?)?;
+
+        loop {
+            let frame = stream.read_frame()?;
+            let packet_id = VarInt::read_from(&mut frame.as_slice())?.0;
+
+            match packet_id {
+                LoginDisconnect::ID => {
+                    let reason = read_packet::<LoginDisconnect>(&frame)?.reason;
+                    return Ok(LoginOutcome::Disconnected(LoginDisconnectReason::parse(reason)));
+                }
+                EncryptionRequest::ID => return Ok(LoginOutcome::OnlineMode),
+                LoginSuccess::ID => {
+                    return Ok(LoginOutcome::OfflineMode {
+                        compression_threshold: stream.compression_threshold(),
+                    })
+                }
+                SetCompression::ID => {
+                    let threshold = read_packet::<SetCompression>(&frame)?.threshold.0;
+                    stream.set_compression(usize::try_from(threshold).ok());
+                }
+                LoginPluginRequest::ID => {
+                    // proxies and mod loaders ask about their own channels, which we know nothing about
+                    let request = read_packet::<LoginPluginRequest>(&frame)?;
+                    log::debug!("declining login plugin request on channel {}", request.channel.0);
+                    stream.write_packet(&LoginPluginResponse {
+                        message_id: request.message_id,
+                        successful: false,
+                    })?;
+                }
+                _ => return Err(ModernPingError::UnexpectedPacket(packet_id)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pinging::{
+        mc_modern::helpers::{ByteArray, Identifier},
+        recording::Recording,
+    };
+
+    fn probe(server: Vec<u8>) -> (Result<LoginOutcome, ModernPingError>, Vec<u8>) {
+        let prober = LoginProber {
+            protocol_version: 765,
+            hostname: "localhost".to_owned(),
+            username: "mcping".to_owned(),
+            timeouts: Timeouts::default(),
+        };
+        let mut recording = Recording::new(server);
+        (prober.ping_stream(&mut recording, 25565), recording.client)
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut server = vec![];
+        let reason = r#"{"translate":"multiplayer.disconnect.not_whitelisted"}"#.to_owned();
+        LoginDisconnect { reason }.write_frame(&mut server).unwrap();
+
+        let (outcome, _) = probe(server);
+        let Ok(LoginOutcome::Disconnected(reason)) = outcome else {
+            panic!("expected a disconnect, got {:?}", outcome);
+        };
+        assert!(reason.not_whitelisted());
+    }
+
+    #[test]
+    fn online_mode() {
+        let mut server = vec![];
+        EncryptionRequest {
+            server_id: String::new(),
+            public_key: ByteArray(vec![1, 2, 3]),
+            verify_token: ByteArray(vec![4, 5, 6, 7]),
+        }
+        .write_frame(&mut server)
+        .unwrap();
+
+        assert!(matches!(probe(server).0, Ok(LoginOutcome::OnlineMode)));
+    }
+
+    #[test]
+    fn offline_mode_after_plugin_request() {
+        let mut server = FramedStream::new(Cursor::new(vec![]));
+        server
+            .write_packet(&LoginPluginRequest {
+                message_id: VarInt(7),
+                channel: Identifier("velocity:player_info".to_owned()),
+            })
+            .unwrap();
+        server.write_packet(&SetCompression { threshold: VarInt(256) }).unwrap();
+        server.set_compression(Some(256));
+        server
+            .write_packet(&LoginSuccess {
+                player_uuid: Uuid(1),
+                name: "mcping".to_owned(),
+            })
+            .unwrap();
+
+        let (outcome, client) = probe(server.into_inner().into_inner());
+        assert!(matches!(
+            outcome,
+            Ok(LoginOutcome::OfflineMode { compression_threshold: Some(256) })
+        ));
+
+        // the plugin request is declined after the handshake and login start
+        assert!(client.ends_with(&[3, LoginPluginResponse::ID as u8, 7, 0]));
+    }
+}
@@ -1,25 +1,80 @@
-use std::{io::{self, Write}, net::TcpStream, time::{Duration, Instant}};
+use std::{io::{self, Read, Write}, time::{Duration, Instant}};
 
-use byteorder::{BigEndian, WriteBytesExt};
 use thiserror::Error;
 
-use self::{helpers::{McModernValue, ProtocolError, VarInt}, ping_json::PingResponse};
+use self::{
+    codec::FramedStream,
+    helpers::{McModernValue, Packet, ProtocolError, VarInt},
+    packets::{Handshake, PingRequest, PongResponse, StatusRequest, StatusResponse, NEXT_STATE_STATUS},
+    ping_json::PingResponse,
+};
 
-use super::Pinger;
+use super::{tcp::{TimedStream, Timeouts}, Pinger, StreamPinger};
+#[cfg(feature = "tokio")]
+use super::{tcp::AsyncTimedStream, AsyncPinger};
 
+pub mod codec;
 pub mod helpers;
+pub mod latency;
+pub mod login;
+pub mod packets;
 pub mod ping_json;
 
 #[derive(Debug)]
 pub struct ModernPingData {
     pub response: PingResponse,
-    pub latency: Duration
+    pub latency: Duration,
+    pub timings: ModernPingTimings
+}
+
+/// Time spent on each phase of a status ping.
+#[derive(Debug)]
+pub struct ModernPingTimings {
+    pub connect: Duration,
+    /// From sending the handshake to receiving the whole status response.
+    pub status: Duration,
+    /// Length of the status response packet, in bytes.
+    pub response_size: usize,
+    pub json_parse: Duration,
+    /// Round trip of the ping request, the same as [`ModernPingData::latency`].
+    pub pong: Duration
 }
 
 pub struct ModernPinger {
     pub protocol_version: i32,
     pub hostname: String,
-    pub read_timeout: Duration
+    pub timeouts: Timeouts
+}
+
+impl ModernPinger {
+    /// The handshake switching to `next_state`, for a server listening on `port`.
+    fn handshake(&self, port: u16, next_state: i32) -> Handshake {
+        Handshake {
+            protocol_version: VarInt(self.protocol_version),
+            server_address: self.hostname.clone(),
+            server_port: port,
+            next_state: VarInt(next_state),
+        }
+    }
+}
+
+/// Reads a packet of type `P` from a frame, without its length.
+fn read_packet<P: Packet>(mut frame: &[u8]) -> Result<P, ModernPingError> {
+    let packet_id = VarInt::read_from(&mut frame)?.0;
+    if packet_id != P::ID {
+        return Err(ModernPingError::WrongId(packet_id, P::ID));
+    }
+
+    Ok(P::read_from(&mut frame)?)
+}
+
+/// Reads the pong response packet, without its length, checking it echoes `payload`.
+fn read_pong_response(frame: &[u8], payload: i64) -> Result<(), ModernPingError> {
+    let echoed_payload = read_packet::<PongResponse>(frame)?.payload;
+    if echoed_payload != payload {
+        return Err(ModernPingError::PongMismatch(echoed_payload, payload));
+    }
+    Ok(())
 }
 
 impl Pinger for ModernPinger {
@@ -29,51 +84,128 @@ impl Pinger for ModernPinger {
 
     fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
         
-        let mut stream = TcpStream::connect(addr)?;
-        stream.set_read_timeout(Some(self.read_timeout))?;
+        let start = Instant::now();
+        let mut stream = TimedStream::connect(addr, self.timeouts)?;
+        stream.get_ref().set_nodelay(true)?; // don't let Nagle's algorithm hold back the ping packet
+        let connect = start.elapsed();
 
-        {
-            let mut handshake_packet = vec![];
+        let mut data = self.ping_stream(&mut stream, addr.port())?;
+        data.timings.connect = connect;
+        Ok(data)
+    }
+}
 
-            VarInt(0x00).write_to(&mut handshake_packet)?;
-            VarInt(self.protocol_version).write_to(&mut handshake_packet)?;
-            self.hostname.write_to(&mut handshake_packet)?;
-            handshake_packet.write_u16::<BigEndian>(addr.port())?;
-            VarInt(1).write_to(&mut handshake_packet)?;
+impl StreamPinger for ModernPinger {
+    /// The reported connect time is zero, as the connection is already established.
+    fn ping_stream<S: Read + Write>(&self, stream: &mut S, port: u16) -> std::result::Result<Self::Data, Self::Error> {
+        self.ping_stream_with_payload(stream, port, rand::random())
+    }
+}
 
-            VarInt(handshake_packet.len() as i32).write_to(&mut stream)?;
-            stream.write_all(&handshake_packet)?;
+impl ModernPinger {
+    /// Like [`StreamPinger::ping_stream`], sending `payload` in the ping request rather than a
+    /// random value, so the exchange can be replayed from a recording.
+    pub fn ping_stream_with_payload<S: Read + Write>(&self, stream: &mut S, port: u16, payload: i64) -> Result<ModernPingData, ModernPingError> {
+        let mut stream = FramedStream::new(stream);
 
-        }
+        let exchange = StatusExchange::start(payload);
+        stream.write_packet(&self.handshake(port, NEXT_STATE_STATUS))?;
+        stream.write_packet(&StatusRequest)?;
 
-        {
-            stream.write_all(&[0x01, 0x00])?; // status request packet
-        }
+        let (ping, exchange) = exchange.receive(&stream.read_frame()?)?;
+        stream.write_packet(&ping)?;
+        exchange.receive(&stream.read_frame()?)
+    }
+}
 
-        let _response_length = VarInt::read_from(&mut stream)?.0;
+#[cfg(feature = "tokio")]
+impl AsyncPinger for ModernPinger {
+    type Data = ModernPingData;
 
-        let packet_id = VarInt::read_from(&mut stream)?.0;
+    type Error = ModernPingError;
 
-        if packet_id != 0x00 {
-            return Err(ModernPingError::WrongId(packet_id, 0x00));
-        }
+    async fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let start = Instant::now();
+        let mut stream = FramedStream::new(AsyncTimedStream::connect(addr, self.timeouts).await?);
+        stream.get_ref().get_ref().set_nodelay(true)?; // don't let Nagle's algorithm hold back the ping packet
+        let connect = start.elapsed();
 
-        let string_data = String::read_from(&mut stream)?;
+        let exchange = StatusExchange::start(rand::random());
+        stream.write_packet(&self.handshake(addr.port(), NEXT_STATE_STATUS)).await?;
+        stream.write_packet(&StatusRequest).await?;
 
-        let data: PingResponse = serde_json::from_str(&string_data)?;
-        
+        let (ping, exchange) = exchange.receive(&stream.read_frame().await?)?;
+        stream.write_packet(&ping).await?;
+        let mut data = exchange.receive(&stream.read_frame().await?)?;
 
-        stream.write_all(&[0x09, 0x01, 0x00, 0x00 ,0x00 ,0x00, 0x00, 0x00, 0x00, 0x00])?; // status request packet
-        let start = Instant::now();
+        data.timings.connect = connect;
+        Ok(data)
+    }
+}
 
-        let _response = VarInt::read_from(&mut stream)?.0;
-        let latency = start.elapsed();
+/// A status ping waiting on the server's status, kept free of I/O so the blocking and the async
+/// pinger share it.
+struct StatusExchange {
+    payload: i64,
+    sent: Instant,
+}
 
+impl StatusExchange {
+    /// Starts timing a status ping, whose handshake and status request are sent right after.
+    fn start(payload: i64) -> Self {
+        Self {
+            payload,
+            sent: Instant::now(),
+        }
+    }
+
+    /// Reads the status response, returning the ping request to send next.
+    fn receive(self, frame: &[u8]) -> Result<(PingRequest, PongExchange), ModernPingError> {
+        let string_data = read_packet::<StatusResponse>(frame)?.json;
+        let status = self.sent.elapsed();
+
+        let start = Instant::now();
+        let response: PingResponse = serde_json::from_str(&string_data)?;
+        let json_parse = start.elapsed();
 
+        let timings = ModernPingTimings {
+            connect: Duration::ZERO,
+            status,
+            response_size: frame.len(),
+            json_parse,
+            pong: Duration::ZERO,
+        };
+        let exchange = PongExchange {
+            payload: self.payload,
+            response,
+            timings,
+            sent: Instant::now(),
+        };
+        Ok((PingRequest { payload: self.payload }, exchange))
+    }
+}
 
+/// A status ping waiting on the pong to its ping request, which is sent right after this is
+/// created.
+struct PongExchange {
+    payload: i64,
+    response: PingResponse,
+    timings: ModernPingTimings,
+    sent: Instant,
+}
 
-        Ok(ModernPingData { response: data, latency })
+impl PongExchange {
+    /// Reads the pong, finishing the ping.
+    fn receive(mut self, frame: &[u8]) -> Result<ModernPingData, ModernPingError> {
+        let latency = self.sent.elapsed();
+        read_pong_response(frame, self.payload)?;
 
+        self.timings.pong = latency;
+        Ok(ModernPingData {
+            response: self.response,
+            latency,
+            timings: self.timings,
+        })
     }
 }
 
@@ -82,6 +214,12 @@ pub enum ModernPingError {
     #[error("received wrong packet id {0}, expected {1}")]
     WrongId(i32, i32),
 
+    #[error("received unexpected packet id {0}")]
+    UnexpectedPacket(i32),
+
+    #[error("pong carried payload {0}, expected {1}")]
+    PongMismatch(i64, i64),
+
     #[error("JSON parse error")]
     JsonError(#[from] serde_json::Error),
 
@@ -91,3 +229,91 @@ pub enum ModernPingError {
     #[error("IO error during ping")]
     IoError(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pinging::recording::Recording;
+
+    const STATUS: &str = r#"{"version":{"name":"Paper 1.20.4","protocol":765},"players":{"max":20,"online":1,"sample":[{"name":"Alice","id":"4566e69f-c907-48ee-8d71-d7ba5aa00d20"}]},"description":{"text":"A Minecraft Server"}}"#;
+
+    fn pinger() -> ModernPinger {
+        ModernPinger {
+            protocol_version: 765,
+            hostname: "localhost".to_owned(),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    /// The server's side of a status ping, answering with `STATUS` and a pong carrying `payload`.
+    fn server(payload: i64) -> Vec<u8> {
+        let mut server = vec![];
+        StatusResponse { json: STATUS.to_owned() }.write_frame(&mut server).unwrap();
+        PongResponse { payload }.write_frame(&mut server).unwrap();
+        server
+    }
+
+    #[test]
+    fn status_ping() {
+        let mut recording = Recording::new(server(42));
+        let data = pinger().ping_stream_with_payload(&mut recording, 25565, 42).unwrap();
+
+        assert_eq!(data.response.version.name, "Paper 1.20.4");
+        assert_eq!(data.response.version.protocol, 765);
+        let players = data.response.players.unwrap();
+        assert_eq!((players.online, players.max), (1, 20));
+        assert_eq!(players.sample[0].name, "Alice");
+        assert_eq!(data.timings.response_size, STATUS.len() + 3);
+
+        let mut client = FramedStream::new(Cursor::new(recording.client));
+        let handshake = read_packet::<Handshake>(&client.read_frame().unwrap()).unwrap();
+        assert_eq!(handshake.protocol_version.0, 765);
+        assert_eq!(handshake.server_address, "localhost");
+        assert_eq!(handshake.server_port, 25565);
+        assert_eq!(handshake.next_state.0, NEXT_STATE_STATUS);
+        read_packet::<StatusRequest>(&client.read_frame().unwrap()).unwrap();
+        assert_eq!(read_packet::<PingRequest>(&client.read_frame().unwrap()).unwrap().payload, 42);
+        assert!(client.read_frame().is_err());
+    }
+
+    #[test]
+    fn pong_mismatch() {
+        let mut recording = Recording::new(server(43));
+        let res = pinger().ping_stream_with_payload(&mut recording, 25565, 42);
+        assert!(matches!(res, Err(ModernPingError::PongMismatch(43, 42))));
+    }
+
+    #[test]
+    fn wrong_status_packet() {
+        let mut server = vec![];
+        PongResponse { payload: 42 }.write_frame(&mut server).unwrap();
+
+        let res = pinger().ping_stream_with_payload(&mut Recording::new(server), 25565, 42);
+        assert!(matches!(res, Err(ModernPingError::WrongId(0x01, 0x00))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_status_ping() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut stream = FramedStream::new(listener.accept().unwrap().0);
+            read_packet::<Handshake>(&stream.read_frame().unwrap()).unwrap();
+            read_packet::<StatusRequest>(&stream.read_frame().unwrap()).unwrap();
+            stream.write_packet(&StatusResponse { json: STATUS.to_owned() }).unwrap();
+
+            let payload = read_packet::<PingRequest>(&stream.read_frame().unwrap()).unwrap().payload;
+            stream.write_packet(&PongResponse { payload }).unwrap();
+        });
+
+        let data = AsyncPinger::ping(&pinger(), addr).await.unwrap();
+        assert_eq!(data.response.version.protocol, 765);
+        assert_eq!(data.timings.response_size, STATUS.len() + 3);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+//! Packets exchanged while pinging and probing a server.
+
+use super::helpers::{ByteArray, Identifier, McModernValue, Uuid, VarInt};
+
+/// [`Handshake::next_state`] for a status ping.
+pub const NEXT_STATE_STATUS: i32 = 1;
+/// [`Handshake::next_state`] for logging in.
+pub const NEXT_STATE_LOGIN: i32 = 2;
+
+/// Opens every connection, serverbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x00)]
+pub struct Handshake {
+    pub protocol_version: VarInt,
+    pub server_address: String,
+    pub server_port: u16,
+    pub next_state: VarInt,
+}
+
+/// Status state, serverbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x00)]
+pub struct StatusRequest;
+
+/// Status state, clientbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x00)]
+pub struct StatusResponse {
+    pub json: String,
+}
+
+/// Status state, serverbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x01)]
+pub struct PingRequest {
+    pub payload: i64,
+}
+
+/// Status state, clientbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x01)]
+pub struct PongResponse {
+    pub payload: i64,
+}
+
+/// Login state, serverbound. This is the layout used since 1.20.2.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x00)]
+pub struct LoginStart {
+    pub name: String,
+    pub player_uuid: Uuid,
+}
+
+/// Login state, serverbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x02)]
+pub struct LoginPluginResponse {
+    pub message_id: VarInt,
+    /// Whether the request was understood. The response data follows if it was.
+    pub successful: bool,
+}
+
+/// Login state, clientbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x00)]
+pub struct LoginDisconnect {
+    /// The reason as a JSON chat component.
+    pub reason: String,
+}
+
+/// Login state, clientbound. Fields added after 1.19 are left out.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x01)]
+pub struct EncryptionRequest {
+    pub server_id: String,
+    pub public_key: ByteArray,
+    pub verify_token: ByteArray,
+}
+
+/// Login state, clientbound. This is the layout used since 1.16, with the properties added in
+/// 1.19 left out.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x02)]
+pub struct LoginSuccess {
+    pub player_uuid: Uuid,
+    pub name: String,
+}
+
+/// Login state, clientbound.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x03)]
+pub struct SetCompression {
+    /// Packets of at least this many bytes are compressed, negative to disable compression.
+    pub threshold: VarInt,
+}
+
+/// Login state, clientbound. The request data follows the channel.
+#[derive(Debug, McModernValue)]
+#[packet(id = 0x04)]
+pub struct LoginPluginRequest {
+    pub message_id: VarInt,
+    pub channel: Identifier,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinging::mc_modern::helpers::Packet;
+
+    #[test]
+    fn handshake_frame() {
+        let handshake = Handshake {
+            protocol_version: VarInt(765),
+            server_address: "mc".to_owned(),
+            server_port: 25565,
+            next_state: VarInt(NEXT_STATE_STATUS),
+        };
+
+        let mut frame = vec![];
+        handshake.write_frame(&mut frame).unwrap();
+        assert_eq!(frame, [9, 0x00, 0xfd, 0x05, 2, b'm', b'c', 0x63, 0xdd, 1]);
+
+        let decoded = Handshake::read_from(&mut &frame[2..]).unwrap();
+        assert_eq!(decoded.protocol_version, VarInt(765));
+        assert_eq!(decoded.server_address, "mc");
+        assert_eq!(decoded.server_port, 25565);
+    }
+
+    #[test]
+    fn unit_packet_frame() {
+        let mut frame = vec![];
+        StatusRequest.write_frame(&mut frame).unwrap();
+        assert_eq!(frame, [1, 0x00]);
+    }
+
+    #[test]
+    fn tuple_struct() {
+        #[derive(Debug, PartialEq, McModernValue)]
+        struct Pair(VarInt, Option<String>);
+
+        let mut data = vec![];
+        Pair(VarInt(300), Some("a".to_owned())).write_to(&mut data).unwrap();
+        assert_eq!(data, [0xac, 0x02, 1, 1, b'a']);
+        assert_eq!(Pair::read_from(&mut data.as_slice()).unwrap(), Pair(VarInt(300), Some("a".to_owned())));
+    }
+}
@@ -45,4 +45,134 @@ pub struct PingResponse {
     pub previews_chat: Option<bool>,
     #[serde(rename = "modinfo")]
     pub mods: Option<PingModInfo>
-}
\ No newline at end of file
+}
+/// Flattens a chat component (such as a server description) into its plain text, keeping
+/// any legacy `§` formatting codes.
+///
+/// Translated components are rendered in English if they are one of the messages a server
+/// commonly disconnects players with, or as their translation key otherwise.
+pub fn plain_text(component: &serde_json::Value) -> String {
+    fn append(component: &serde_json::Value, out: &mut String) {
+        match component {
+            serde_json::Value::String(v) => out.push_str(v),
+            serde_json::Value::Array(v) => v.iter().for_each(|v| append(v, out)),
+            serde_json::Value::Object(v) => {
+                if let Some(serde_json::Value::String(text)) = v.get("text") {
+                    out.push_str(text);
+                }
+                if let Some(serde_json::Value::String(key)) = v.get("translate") {
+                    let args = match v.get("with") {
+                        Some(serde_json::Value::Array(with)) => with.iter().map(plain_text).collect(),
+                        _ => vec![],
+                    };
+                    out.push_str(&translate(key, &args));
+                }
+                if let Some(extra) = v.get("extra") {
+                    append(extra, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    append(component, &mut out);
+    out
+}
+
+/// English text of the messages vanilla servers disconnect players with.
+const TRANSLATIONS: &[(&str, &str)] = &[
+    ("multiplayer.disconnect.banned", "You are banned from this server."),
+    ("multiplayer.disconnect.banned.expiration", "\nYour ban will be removed on %s"),
+    ("multiplayer.disconnect.banned.reason", "You are banned from this server.\nReason: %s"),
+    ("multiplayer.disconnect.banned_ip.expiration", "\nYour ban will be removed on %s"),
+    ("multiplayer.disconnect.banned_ip.reason", "Your IP address is banned from this server.\nReason: %s"),
+    ("multiplayer.disconnect.duplicate_login", "You logged in from another location"),
+    ("multiplayer.disconnect.incompatible", "Incompatible client! Please use %s"),
+    ("multiplayer.disconnect.name_taken", "That name is already taken"),
+    ("multiplayer.disconnect.not_whitelisted", "You are not white-listed on this server!"),
+    ("multiplayer.disconnect.outdated_client", "Incompatible client! Please use %s"),
+    ("multiplayer.disconnect.outdated_server", "Incompatible client! Please use %s"),
+    ("multiplayer.disconnect.server_full", "The server is full!"),
+    ("multiplayer.disconnect.server_shutdown", "Server closed"),
+    ("multiplayer.disconnect.unverified_username", "Failed to verify username!"),
+];
+
+/// Renders a translation key with its arguments, or returns the key if it is not known.
+fn translate(key: &str, args: &[String]) -> String {
+    match TRANSLATIONS.iter().find(|(v, _)| *v == key) {
+        Some((_, format)) => format_translation(format, args),
+        None => key.to_owned(),
+    }
+}
+
+/// Fills in the `%s` and `%1$s` placeholders of a translation.
+fn format_translation(format: &str, args: &[String]) -> String {
+    let mut out = String::new();
+    let mut next_arg = 0;
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        // either `%s`, taking the next argument, or `%<n>$s`, taking the nth
+        let index = if let Some(after) = rest.strip_prefix('s') {
+            rest = after;
+            next_arg += 1;
+            next_arg - 1
+        } else if let Some((n, after)) = rest
+            .split_once("$s")
+            .and_then(|(n, after)| Some((n.parse::<usize>().ok()?, after)))
+        {
+            rest = after;
+            n.saturating_sub(1)
+        } else {
+            out.push('%');
+            continue;
+        };
+        out.push_str(args.get(index).map_or("", |v| v.as_str()));
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn plain_text_extra() {
+        let component = json!({"text": "A ", "extra": [{"text": "§aserver"}, " here"]});
+        assert_eq!(plain_text(&component), "A §aserver here");
+    }
+
+    #[test]
+    fn plain_text_translate() {
+        let component = json!({"translate": "multiplayer.disconnect.outdated_client", "with": ["1.20.4"]});
+        assert_eq!(plain_text(&component), "Incompatible client! Please use 1.20.4");
+
+        let component = json!({
+            "translate": "multiplayer.disconnect.banned.reason",
+            "with": [{"text": "griefing"}],
+            "extra": [{"translate": "multiplayer.disconnect.banned.expiration", "with": ["tomorrow"]}]
+        });
+        assert_eq!(
+            plain_text(&component),
+            "You are banned from this server.\nReason: griefing\nYour ban will be removed on tomorrow"
+        );
+
+        let component = json!({"translate": "some.plugin.message"});
+        assert_eq!(plain_text(&component), "some.plugin.message");
+    }
+
+    #[test]
+    fn translation_placeholders() {
+        let args = ["a".to_owned(), "b".to_owned()];
+        assert_eq!(format_translation("%s, %s", &args), "a, b");
+        assert_eq!(format_translation("%2$s then %1$s", &args), "b then a");
+        assert_eq!(format_translation("%s %s %s", &args), "a b ");
+        assert_eq!(format_translation("100% sure", &args), "100% sure");
+    }
+}
@@ -1,64 +1,586 @@
-use std::{collections::HashMap, ffi::CStr, io::{self, ErrorKind}, net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket}, time::Duration};
+use std::{collections::HashMap, io, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket}, sync::Mutex, time::{Duration, Instant}};
 
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use thiserror::Error;
 
-use super::Pinger;
+use super::{udp::recv_until, Pinger};
+#[cfg(feature = "tokio")]
+use super::AsyncPinger;
 
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE_TYPE: u8 = 0x09;
+const STAT_TYPE: u8 = 0x00;
 
+/// Type byte and session id, which prefix every response.
+const HEADER_LEN: usize = 5;
 
+/// Session ids must only use the lower 4 bits of each byte.
+const SESSION_ID_MASK: i32 = 0x0F0F0F0F;
+
+/// Challenge tokens expire after around 30 seconds; refresh them a little earlier.
+const CHALLENGE_TOKEN_LIFETIME: Duration = Duration::from_secs(25);
+
+/// `splitnum\0\x80\0`, sent before the K/V section of a full stat.
+const KV_SECTION_PADDING: usize = 11;
+
+/// Sent between the K/V section and the player list of a full stat.
+const PLAYER_SECTION_PADDING: &[u8] = b"\x01player_\x00\x00";
+
+#[derive(Debug)]
 pub struct QueryData {
     pub hostname: String,
     pub game_type: String,
     pub version: String,
-    pub plugins: Option<String>,
+    pub plugins: Option<QueryPlugins>,
     pub map: String,
     pub num_players: String,
     pub max_players: String,
     pub host_port: String,
     pub host_ip: String,
+    pub players: Vec<String>,
     pub unrecognised: HashMap<String, String>
 }
 
+impl QueryData {
+    /// Builds the query data from the K/V section and player list of a full stat response.
+    fn from_pairs(mut pairs: HashMap<String, String>, players: Vec<String>) -> Result<Self, QueryPingError> {
+        let mut take = |key: &'static str| pairs.remove(key).ok_or(QueryPingError::MissingField(key));
+
+        Ok(Self {
+            hostname: take("hostname")?,
+            game_type: take("gametype")?,
+            version: take("version")?,
+            plugins: take("plugins").ok().filter(|v| !v.is_empty()).map(QueryPlugins::parse),
+            map: take("map")?,
+            num_players: take("numplayers")?,
+            max_players: take("maxplayers")?,
+            host_port: take("hostport")?,
+            host_ip: take("hostip")?,
+            players,
+            unrecognised: pairs,
+        })
+    }
+}
+
+
+#[derive(Debug)]
+pub struct QueryPlugin {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// The `plugins` field of a full stat, in the Bukkit format
+/// `"CraftBukkit on Bukkit 1.20.4: WorldEdit 7.2; Vault 1.7"`.
+#[derive(Debug)]
+pub struct QueryPlugins {
+    pub raw: String,
+    pub server_software: String,
+    pub server_version: Option<String>,
+    pub plugins: Vec<QueryPlugin>,
+}
+
+impl QueryPlugins {
+    pub fn parse(raw: String) -> Self {
+        // splits `"Name With Spaces 1.0"` into its name and trailing version
+        fn split_version(v: &str) -> (String, Option<String>) {
+            match v.trim().rsplit_once(' ') {
+                Some((name, version)) => (name.trim_end().to_owned(), Some(version.to_owned())),
+                None => (v.trim().to_owned(), None),
+            }
+        }
+
+        let (server, plugins) = raw.split_once(':').unwrap_or((&raw, ""));
+
+        let (server_software, server_version) = split_version(server);
+
+        let plugins = plugins
+            .split(';')
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| {
+                let (name, version) = split_version(v);
+                QueryPlugin { name, version }
+            })
+            .collect();
+
+        Self {
+            server_software,
+            server_version,
+            plugins,
+            raw,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BasicQueryData {
+    pub motd: String,
+    pub game_type: String,
+    pub map: String,
+    pub num_players: String,
+    pub max_players: String,
+    pub host_port: u16,
+    pub host_ip: String,
+}
+
+/// The stat returned by a [`QueryPinger`], depending on its [`QueryMode`].
+#[derive(Debug)]
+pub enum QueryResponse {
+    Full(Box<QueryData>),
+    Basic(BasicQueryData),
+}
+
+/// Which stat request a [`QueryPinger`] sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Only request a full stat.
+    Full,
+    /// Only request a basic stat.
+    Basic,
+    /// Request a full stat, falling back to a basic stat if the server does not answer it in time.
+    FullOrBasic,
+}
+
+/// How many times, and for how long, a [`QueryPinger`] sends a request before giving up.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of times the request is sent.
+    pub attempts: u32,
+    /// How long to wait for a response to each attempt.
+    pub timeout: Duration,
+}
+
+/// Number of requests sent before the server answered.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct QueryAttempts {
+    pub handshake: u32,
+    pub stat: u32,
+}
+
+#[derive(Debug)]
+pub struct QueryPingData {
+    pub response: QueryResponse,
+    pub attempts: QueryAttempts,
+}
 
 pub struct QueryPinger {
-    pub read_timeout: Duration
+    /// Local address to send requests from. Defaults to the unspecified address of the
+    /// server address' family.
+    pub local_addr: Option<IpAddr>,
+    pub handshake_retry: RetryPolicy,
+    pub stat_retry: RetryPolicy,
+    pub mode: QueryMode,
+}
+
+struct ChallengeToken {
+    token: i32,
+    obtained: Instant,
+}
+
+/// State kept with a server between pings.
+struct QuerySession {
+    id: i32,
+    challenge_token: Option<ChallengeToken>,
+}
+
+impl QuerySession {
+    fn new() -> Self {
+        Self {
+            id: rand::random::<i32>() & SESSION_ID_MASK,
+            challenge_token: None,
+        }
+    }
+}
+
+/// A request of a query, sent until a response carrying its session id and type arrives.
+struct QueryRequest {
+    session_id: i32,
+    ty: u8,
+    payload: Vec<u8>,
+    timeout: Duration,
+}
+
+impl QueryRequest {
+    /// Sends the request and waits for its response, returning the response payload.
+    fn send(&self, socket: &UdpSocket) -> Result<Vec<u8>, QueryPingError> {
+        socket.send(&encode_request(self.ty, self.session_id, &self.payload)?)?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut data = vec![0; u16::MAX as usize];
+        loop {
+            let Some(read) = recv_until(socket, &mut data, deadline)? else {
+                return Err(QueryPingError::TimeoutReached);
+            };
+            if let Some(payload) = decode_response(&data[..read], self.session_id, self.ty)? {
+                return Ok(payload.to_vec());
+            }
+        }
+    }
+
+    /// Async counterpart of [`QueryRequest::send`].
+    #[cfg(feature = "tokio")]
+    async fn send_async(&self, socket: &tokio::net::UdpSocket) -> Result<Vec<u8>, QueryPingError> {
+        socket.send(&encode_request(self.ty, self.session_id, &self.payload)?).await?;
+
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let mut data = vec![0; u16::MAX as usize];
+        loop {
+            let read = match tokio::time::timeout_at(deadline, socket.recv(&mut data)).await {
+                Ok(read) => read?,
+                Err(_) => return Err(QueryPingError::TimeoutReached),
+            };
+            if let Some(payload) = decode_response(&data[..read], self.session_id, self.ty)? {
+                return Ok(payload.to_vec());
+            }
+        }
+    }
+}
+
+/// What a [`QueryExchange`] needs next.
+enum QueryStep {
+    Send(QueryRequest),
+    Done(QueryPingData),
+}
+
+/// The request the exchange is waiting on a response to.
+#[derive(Clone, Copy)]
+enum Awaiting {
+    Handshake { attempt: u32 },
+    Stat,
+}
+
+/// The requests of a single ping, with their retries and the basic stat fallback, kept free of
+/// I/O so the blocking and the async pinger drive the same exchange.
+struct QueryExchange<'a> {
+    pinger: &'a QueryPinger,
+    session: &'a mut QuerySession,
+    started: Instant,
+    attempts: QueryAttempts,
+    full: bool,
+    stat_attempt: u32,
+    awaiting: Awaiting,
+}
+
+impl<'a> QueryExchange<'a> {
+    /// Starts a ping, returning the first request to send.
+    fn start(pinger: &'a QueryPinger, session: &'a mut QuerySession) -> Result<(Self, QueryRequest), QueryPingError> {
+        let mut exchange = Self {
+            pinger,
+            session,
+            started: Instant::now(),
+            attempts: QueryAttempts::default(),
+            full: pinger.mode != QueryMode::Basic,
+            stat_attempt: 0,
+            awaiting: Awaiting::Stat,
+        };
+        let request = exchange.next_stat()?;
+        Ok((exchange, request))
+    }
+
+    /// Takes the outcome of the last request, returning the next request or the ping's result.
+    fn receive(&mut self, response: Result<Vec<u8>, QueryPingError>) -> Result<QueryStep, QueryPingError> {
+        match (self.awaiting, response) {
+            (Awaiting::Handshake { attempt }, Err(QueryPingError::TimeoutReached))
+                if attempt < self.pinger.handshake_retry.attempts =>
+            {
+                log::debug!("handshake timed out, retrying ({}/{})", attempt, self.pinger.handshake_retry.attempts);
+                Ok(QueryStep::Send(self.handshake(attempt + 1)))
+            }
+            (Awaiting::Handshake { .. }, Ok(data)) => {
+                let token = parse_challenge_token(&data)?;
+                self.session.challenge_token = Some(ChallengeToken {
+                    token,
+                    obtained: Instant::now(),
+                });
+                Ok(QueryStep::Send(self.stat(token)?))
+            }
+            (Awaiting::Stat, Err(QueryPingError::TimeoutReached))
+                if self.stat_attempt < self.pinger.stat_retry.attempts =>
+            {
+                log::debug!("stat request timed out, retrying ({}/{})", self.stat_attempt, self.pinger.stat_retry.attempts);
+
+                // servers silently ignore stat requests with a rejected token, so don't trust
+                // one carried over from an earlier ping after a timeout
+                if self.session.challenge_token.as_ref().is_some_and(|v| v.obtained < self.started) {
+                    self.session.challenge_token = None;
+                }
+                Ok(QueryStep::Send(self.next_stat()?))
+            }
+            (Awaiting::Stat, Ok(data)) => {
+                let response = if self.full {
+                    QueryResponse::Full(Box::new(parse_full_stat(&data)?))
+                } else {
+                    QueryResponse::Basic(parse_basic_stat(&data)?)
+                };
+                Ok(QueryStep::Done(QueryPingData {
+                    response,
+                    attempts: self.attempts,
+                }))
+            }
+            (_, Err(QueryPingError::TimeoutReached)) if self.full && self.pinger.mode == QueryMode::FullOrBasic => {
+                log::debug!("full stat timed out, falling back to basic stat");
+                self.full = false;
+                self.stat_attempt = 0;
+
+                // a late full stat would pass as the basic stat's response, so start a session
+                // it is not part of
+                *self.session = QuerySession::new();
+                Ok(QueryStep::Send(self.next_stat()?))
+            }
+            (_, Err(e)) => Err(e),
+        }
+    }
+
+    /// The next attempt at a stat request, preceded by a handshake if the session has no
+    /// challenge token or it has expired.
+    fn next_stat(&mut self) -> Result<QueryRequest, QueryPingError> {
+        self.stat_attempt += 1;
+        match &self.session.challenge_token {
+            Some(v) if v.obtained.elapsed() < CHALLENGE_TOKEN_LIFETIME => {
+                let token = v.token;
+                self.stat(token)
+            }
+            _ => Ok(self.handshake(1)),
+        }
+    }
+
+    fn handshake(&mut self, attempt: u32) -> QueryRequest {
+        self.attempts.handshake += 1;
+        self.awaiting = Awaiting::Handshake { attempt };
+        QueryRequest {
+            session_id: self.session.id,
+            ty: HANDSHAKE_TYPE,
+            payload: vec![],
+            timeout: self.pinger.handshake_retry.timeout,
+        }
+    }
+
+    fn stat(&mut self, challenge_token: i32) -> Result<QueryRequest, QueryPingError> {
+        self.attempts.stat += 1;
+        self.awaiting = Awaiting::Stat;
+        Ok(QueryRequest {
+            session_id: self.session.id,
+            ty: STAT_TYPE,
+            payload: stat_payload(challenge_token, self.full)?,
+            timeout: self.pinger.stat_retry.timeout,
+        })
+    }
 }
 
+impl QueryPinger {
+    /// The address to bind to for querying `addr`.
+    fn local_addr(&self, addr: SocketAddr) -> SocketAddr {
+        let ip = self.local_addr.unwrap_or(match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+        SocketAddr::new(ip, 0)
+    }
+
+    fn connect(&self, addr: SocketAddr) -> Result<UdpSocket, QueryPingError> {
+        let socket = UdpSocket::bind(self.local_addr(addr))?;
+        socket.connect(addr)?;
+        Ok(socket)
+    }
+
+    fn query(&self, socket: &UdpSocket, session: &mut QuerySession) -> Result<QueryPingData, QueryPingError> {
+        let (mut exchange, mut request) = QueryExchange::start(self, session)?;
+        loop {
+            match exchange.receive(request.send(socket))? {
+                QueryStep::Send(next) => request = next,
+                QueryStep::Done(data) => return Ok(data),
+            }
+        }
+    }
+}
 
 impl Pinger for QueryPinger {
-    type Data = QueryData;
+    type Data = QueryPingData;
 
     type Error = QueryPingError;
 
     fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
-        
-            
-        let mut socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))?;
-        socket.connect(addr)?;
-        socket.set_read_timeout(Some(self.read_timeout))?;
+        self.query(&self.connect(addr)?, &mut QuerySession::new())
+    }
+}
 
-        socket.send(&[0xFE, 0xFD, 0x09, 0x00, 0x00, 0x00, 0x01])?; // handshake with session id 1
+/// A [`QueryPinger`] which keeps its session with each server between pings, reusing the
+/// challenge token until it expires.
+///
+/// Repeated pings of the same server skip the handshake, halving the packet count.
+pub struct QueryClient {
+    pub pinger: QueryPinger,
+    sessions: Mutex<HashMap<SocketAddr, (UdpSocket, QuerySession)>>,
+}
 
-        
-        
+impl QueryClient {
+    pub fn new(pinger: QueryPinger) -> Self {
+        Self {
+            pinger,
+            sessions: Mutex::default(),
+        }
+    }
 
-        let mut data = [0; 64];
-        let challenge_token = match socket.recv(&mut data) {
-            Ok(read) => {
-                let data = &data[9..read];
-                let data = CStr::from_bytes_with_nul(data).map_err(|_| QueryPingError::BadResponseString)?.to_string_lossy();
-                data.parse::<i32>().map_err(|_| QueryPingError::BadResponseString)?
-            }
-            Err(e) if e.kind() == ErrorKind::TimedOut => return Err(QueryPingError::TimeoutReached),
-            Err(e) => return Err(QueryPingError::IoError(e))
+    /// Forgets all cached sessions.
+    pub fn clear(&self) {
+        self.sessions.lock().unwrap().clear();
+    }
+}
+
+impl Pinger for QueryClient {
+    type Data = QueryPingData;
+
+    type Error = QueryPingError;
+
+    fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        // taken out of the map so pings of other servers are not held up
+        let session = self.sessions.lock().unwrap().remove(&addr);
+        let (socket, mut session) = match session {
+            Some(v) => v,
+            None => (self.pinger.connect(addr)?, QuerySession::new()),
         };
-        
 
+        let data = self.pinger.query(&socket, &mut session)?;
 
-        todo!()
+        self.sessions.lock().unwrap().insert(addr, (socket, session));
+        Ok(data)
     }
 }
 
+#[cfg(feature = "tokio")]
+impl AsyncPinger for QueryPinger {
+    type Data = QueryPingData;
+
+    type Error = QueryPingError;
+
+    async fn ping(&self, addr: std::net::SocketAddr) -> std::result::Result<Self::Data, Self::Error> {
+        let socket = tokio::net::UdpSocket::bind(self.local_addr(addr)).await?;
+        socket.connect(addr).await?;
+
+        let mut session = QuerySession::new();
+        let (mut exchange, mut request) = QueryExchange::start(self, &mut session)?;
+        loop {
+            match exchange.receive(request.send_async(&socket).await)? {
+                QueryStep::Send(next) => request = next,
+                QueryStep::Done(data) => return Ok(data),
+            }
+        }
+    }
+}
+
+/// Builds a request packet of the given type.
+fn encode_request(ty: u8, session_id: i32, payload: &[u8]) -> Result<Vec<u8>, QueryPingError> {
+    let mut request = Vec::with_capacity(7 + payload.len());
+    request.extend_from_slice(&MAGIC);
+    request.push(ty);
+    request.write_i32::<BigEndian>(session_id)?;
+    request.extend_from_slice(payload);
+    Ok(request)
+}
+
+/// Checks the header of a response, returning its payload, or `None` if the datagram belongs
+/// to a different session or request.
+fn decode_response(data: &[u8], session_id: i32, ty: u8) -> Result<Option<&[u8]>, QueryPingError> {
+    if data.len() < HEADER_LEN {
+        return Err(QueryPingError::PacketTooShort(data.len()));
+    }
+
+    let response_session_id = BigEndian::read_i32(&data[1..HEADER_LEN]);
+    if response_session_id != session_id {
+        log::debug!("discarding datagram for session id {}", response_session_id);
+        return Ok(None);
+    }
+
+    // such as a late response to an earlier attempt at another request
+    if data[0] != ty {
+        log::debug!("discarding datagram of type {:#04x}, expected {:#04x}", data[0], ty);
+        return Ok(None);
+    }
+
+    Ok(Some(&data[HEADER_LEN..]))
+}
+
+fn parse_challenge_token(mut data: &[u8]) -> Result<i32, QueryPingError> {
+    read_string(&mut data)?.parse::<i32>().map_err(|_| QueryPingError::BadResponseString)
+}
+
+fn stat_payload(challenge_token: i32, full: bool) -> Result<Vec<u8>, QueryPingError> {
+    let mut payload = Vec::with_capacity(8);
+    payload.write_i32::<BigEndian>(challenge_token)?;
+    if full {
+        payload.extend_from_slice(&[0x00; 4]); // padding, requests a full stat
+    }
+    Ok(payload)
+}
+
+fn parse_full_stat(data: &[u8]) -> Result<QueryData, QueryPingError> {
+    let mut payload = data.get(KV_SECTION_PADDING..).ok_or(QueryPingError::BadResponseString)?;
+
+    let mut pairs = HashMap::new();
+    loop {
+        let key = read_string(&mut payload)?;
+        if key.is_empty() {
+            break;
+        }
+        let value = read_string(&mut payload)?;
+        pairs.insert(key, value);
+    }
+
+    let players = read_players(payload)?;
+
+    QueryData::from_pairs(pairs, players)
+}
+
+fn parse_basic_stat(mut payload: &[u8]) -> Result<BasicQueryData, QueryPingError> {
+    let motd = read_string(&mut payload)?;
+    let game_type = read_string(&mut payload)?;
+    let map = read_string(&mut payload)?;
+    let num_players = read_string(&mut payload)?;
+    let max_players = read_string(&mut payload)?;
+    let host_port = payload.read_u16::<LittleEndian>().map_err(|_| QueryPingError::BadResponseString)?;
+    let host_ip = read_string(&mut payload)?;
+
+    Ok(BasicQueryData {
+        motd,
+        game_type,
+        map,
+        num_players,
+        max_players,
+        host_port,
+        host_ip,
+    })
+}
+
+/// Reads a NUL-terminated string off the front of `data`, advancing past the terminator.
+fn read_string(data: &mut &[u8]) -> Result<String, QueryPingError> {
+    let end = data.iter().position(|&b| b == 0).ok_or(QueryPingError::BadResponseString)?;
+    let string = String::from_utf8_lossy(&data[..end]).into_owned();
+    *data = &data[end + 1..];
+    Ok(string)
+}
+
+/// Reads the player list section which follows the K/V section of a full stat.
+fn read_players(mut data: &[u8]) -> Result<Vec<String>, QueryPingError> {
+    // some implementations emit extra NULs after the K/V terminator
+    while let [0, rest @ ..] = data {
+        data = rest;
+    }
+
+    // ..and some leave the player section out entirely
+    if data.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let data = data.strip_prefix(PLAYER_SECTION_PADDING).ok_or(QueryPingError::BadResponseString)?;
+
+    // names are NUL-terminated and the list ends with an empty name, though the
+    // final terminator is not always present
+    Ok(data
+        .split(|&b| b == 0)
+        .take_while(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect())
+}
+
 
 
 
@@ -68,7 +590,345 @@ pub enum QueryPingError {
     TimeoutReached,
     #[error("Bad string data received.")]
     BadResponseString,
+    #[error("Stat response is missing the {0:?} field.")]
+    MissingField(&'static str),
+    #[error("Received a {0} byte packet, which is too short to be a response.")]
+    PacketTooShort(usize),
 
     #[error("IO error during ping")]
     IoError(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A full stat payload with the required K/V pairs, followed by `tail`.
+    fn full_stat(tail: &[u8]) -> Vec<u8> {
+        let mut data = b"splitnum\0\x80\0".to_vec();
+        assert_eq!(data.len(), KV_SECTION_PADDING);
+        for (key, value) in [
+            ("hostname", "A Minecraft Server"),
+            ("gametype", "SMP"),
+            ("version", "1.20.4"),
+            ("plugins", ""),
+            ("map", "world"),
+            ("numplayers", "2"),
+            ("maxplayers", "20"),
+            ("hostport", "25565"),
+            ("hostip", "127.0.0.1"),
+        ] {
+            data.extend_from_slice(key.as_bytes());
+            data.push(0);
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        data.push(0); // K/V section terminator
+        data.extend_from_slice(tail);
+        data
+    }
+
+    fn player_section(names: &[u8]) -> Vec<u8> {
+        [PLAYER_SECTION_PADDING, names].concat()
+    }
+
+    #[test]
+    fn full_stat_players() {
+        let data = full_stat(&player_section(b"Alice\0Bob\0\0"));
+        let stat = parse_full_stat(&data).unwrap();
+        assert_eq!(stat.players, ["Alice", "Bob"]);
+        assert_eq!(stat.hostname, "A Minecraft Server");
+        assert!(stat.plugins.is_none());
+        assert!(stat.unrecognised.is_empty());
+    }
+
+    #[test]
+    fn full_stat_no_players() {
+        let data = full_stat(&player_section(b"\0"));
+        assert!(parse_full_stat(&data).unwrap().players.is_empty());
+    }
+
+    #[test]
+    fn full_stat_missing_player_section() {
+        let data = full_stat(&[]);
+        assert!(parse_full_stat(&data).unwrap().players.is_empty());
+    }
+
+    #[test]
+    fn full_stat_extra_nuls() {
+        let data = full_stat(&[b"\0\0\0".as_slice(), &player_section(b"Alice\0\0")].concat());
+        assert_eq!(parse_full_stat(&data).unwrap().players, ["Alice"]);
+
+        let data = full_stat(b"\0\0");
+        assert!(parse_full_stat(&data).unwrap().players.is_empty());
+    }
+
+    #[test]
+    fn players_without_final_terminator() {
+        assert_eq!(read_players(&player_section(b"Alice\0Bob\0")).unwrap(), ["Alice", "Bob"]);
+        assert_eq!(read_players(&player_section(b"Alice\0Bob")).unwrap(), ["Alice", "Bob"]);
+        assert!(read_players(&player_section(b"")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn players_bad_padding() {
+        assert!(matches!(read_players(b"\x01players\0Alice\0\0"), Err(QueryPingError::BadResponseString)));
+    }
+
+    #[test]
+    fn plugins_bukkit() {
+        let plugins = QueryPlugins::parse("CraftBukkit on Bukkit 1.20.4: WorldEdit 7.2; Vault 1.7".to_owned());
+        assert_eq!(plugins.server_software, "CraftBukkit on Bukkit");
+        assert_eq!(plugins.server_version.as_deref(), Some("1.20.4"));
+
+        let names = plugins.plugins.iter().map(|v| (v.name.as_str(), v.version.as_deref())).collect::<Vec<_>>();
+        assert_eq!(names, [("WorldEdit", Some("7.2")), ("Vault", Some("1.7"))]);
+        assert_eq!(plugins.raw, "CraftBukkit on Bukkit 1.20.4: WorldEdit 7.2; Vault 1.7");
+    }
+
+    #[test]
+    fn plugins_without_colon() {
+        let plugins = QueryPlugins::parse("Vanilla".to_owned());
+        assert_eq!(plugins.server_software, "Vanilla");
+        assert_eq!(plugins.server_version, None);
+        assert!(plugins.plugins.is_empty());
+
+        let plugins = QueryPlugins::parse("CraftBukkit on Bukkit 1.20.4".to_owned());
+        assert_eq!(plugins.server_software, "CraftBukkit on Bukkit");
+        assert_eq!(plugins.server_version.as_deref(), Some("1.20.4"));
+        assert!(plugins.plugins.is_empty());
+
+        let plugins = QueryPlugins::parse("Paper 1.20.4: ".to_owned());
+        assert!(plugins.plugins.is_empty());
+    }
+
+    #[test]
+    fn plugin_without_version() {
+        let plugins = QueryPlugins::parse("Paper 1.20.4: Essentials; LuckPerms 5.4".to_owned());
+        let names = plugins.plugins.iter().map(|v| (v.name.as_str(), v.version.as_deref())).collect::<Vec<_>>();
+        assert_eq!(names, [("Essentials", None), ("LuckPerms", Some("5.4"))]);
+    }
+
+    #[test]
+    fn response_header() {
+        let data = [STAT_TYPE, 0x01, 0x02, 0x03, 0x04, b'x'];
+        assert_eq!(decode_response(&data, 0x01020304, STAT_TYPE).unwrap(), Some(&b"x"[..]));
+    }
+
+    #[test]
+    fn response_for_other_session() {
+        let data = [STAT_TYPE, 0x01, 0x02, 0x03, 0x05, b'x'];
+        assert_eq!(decode_response(&data, 0x01020304, STAT_TYPE).unwrap(), None);
+    }
+
+    #[test]
+    fn response_wrong_type() {
+        let data = [HANDSHAKE_TYPE, 0x01, 0x02, 0x03, 0x04, b'x'];
+        assert_eq!(decode_response(&data, 0x01020304, STAT_TYPE).unwrap(), None);
+    }
+
+    #[test]
+    fn response_too_short() {
+        for len in 0..HEADER_LEN {
+            let data = vec![STAT_TYPE; len];
+            assert!(matches!(
+                decode_response(&data, 0x01020304, STAT_TYPE),
+                Err(QueryPingError::PacketTooShort(v)) if v == len
+            ));
+        }
+
+        // a bare header is a valid, empty response
+        let data = [STAT_TYPE, 0x01, 0x02, 0x03, 0x04];
+        assert_eq!(decode_response(&data, 0x01020304, STAT_TYPE).unwrap(), Some(&[][..]));
+    }
+
+    /// A basic stat payload.
+    fn basic_stat() -> Vec<u8> {
+        let mut data = ["A Minecraft Server", "SMP", "world", "2", "20", ""].join("\0").into_bytes();
+        data.extend_from_slice(&25565_u16.to_le_bytes());
+        data.extend_from_slice(b"127.0.0.1\0");
+        data
+    }
+
+    #[test]
+    fn exchange_retries_then_falls_back() {
+        let retry = RetryPolicy {
+            attempts: 2,
+            timeout: Duration::from_secs(5),
+        };
+        let pinger = QueryPinger {
+            local_addr: None,
+            handshake_retry: retry,
+            stat_retry: retry,
+            mode: QueryMode::FullOrBasic,
+        };
+        let mut session = QuerySession::new();
+        let expect_send = |step: Result<QueryStep, QueryPingError>| match step {
+            Ok(QueryStep::Send(request)) => request,
+            _ => panic!("expected another request"),
+        };
+
+        let (mut exchange, request) = QueryExchange::start(&pinger, &mut session).unwrap();
+        assert_eq!(request.ty, HANDSHAKE_TYPE);
+        let request = expect_send(exchange.receive(Err(QueryPingError::TimeoutReached)));
+        assert_eq!(request.ty, HANDSHAKE_TYPE);
+
+        let request = expect_send(exchange.receive(Ok(b"9513307\0".to_vec())));
+        assert_eq!((request.ty, request.payload.len()), (STAT_TYPE, 8));
+        let request = expect_send(exchange.receive(Err(QueryPingError::TimeoutReached)));
+        assert_eq!((request.ty, request.payload.len()), (STAT_TYPE, 8));
+
+        // out of full stat attempts, so a basic stat is requested in a new session
+        let session_id = request.session_id;
+        let request = expect_send(exchange.receive(Err(QueryPingError::TimeoutReached)));
+        assert_eq!(request.ty, HANDSHAKE_TYPE);
+        assert_ne!(request.session_id, session_id);
+        let request = expect_send(exchange.receive(Ok(b"9513308\0".to_vec())));
+        assert_eq!(request.ty, STAT_TYPE);
+        assert_eq!(request.payload, 9513308_i32.to_be_bytes());
+
+        let Ok(QueryStep::Done(data)) = exchange.receive(Ok(basic_stat())) else {
+            panic!("expected the ping to finish");
+        };
+        let QueryResponse::Basic(stat) = data.response else {
+            panic!("expected a basic stat");
+        };
+        assert_eq!((stat.motd.as_str(), stat.host_port), ("A Minecraft Server", 25565));
+        assert_eq!((data.attempts.handshake, data.attempts.stat), (3, 3));
+    }
+
+    /// Answers `requests` requests on a local socket, like a server handing out the challenge
+    /// token 9513307 and a full stat.
+    ///
+    /// With `late_handshake`, each stat response is preceded by a second handshake response, as
+    /// when a retried handshake is answered twice.
+    fn serve(requests: usize, late_handshake: bool) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut data = [0; 1500];
+            for _ in 0..requests {
+                let (_, from) = socket.recv_from(&mut data).unwrap();
+                assert_eq!(data[..2], MAGIC);
+
+                let ty = data[2];
+                let mut response = vec![ty];
+                response.extend_from_slice(&data[3..7]); // session id
+                if ty == HANDSHAKE_TYPE {
+                    response.extend_from_slice(b"9513307\0");
+                } else {
+                    assert_eq!(data[7..11], 9513307_i32.to_be_bytes());
+                    if late_handshake {
+                        let handshake = [&[HANDSHAKE_TYPE], &data[3..7], b"9513307\0"].concat();
+                        socket.send_to(&handshake, from).unwrap();
+                    }
+                    response.extend_from_slice(&full_stat(&player_section(b"Alice\0\0")));
+                }
+                socket.send_to(&response, from).unwrap();
+            }
+        });
+        addr
+    }
+
+    fn pinger() -> QueryPinger {
+        let retry = RetryPolicy {
+            attempts: 1,
+            timeout: Duration::from_secs(5),
+        };
+        QueryPinger {
+            local_addr: None,
+            handshake_retry: retry,
+            stat_retry: retry,
+            mode: QueryMode::Full,
+        }
+    }
+
+    #[test]
+    fn query() {
+        let data = Pinger::ping(&pinger(), serve(2, false)).unwrap();
+
+        let QueryResponse::Full(stat) = data.response else {
+            panic!("expected a full stat");
+        };
+        assert_eq!(stat.players, ["Alice"]);
+        assert_eq!((data.attempts.handshake, data.attempts.stat), (1, 1));
+    }
+
+    #[test]
+    fn late_handshake_response() {
+        let data = Pinger::ping(&pinger(), serve(2, true)).unwrap();
+        assert!(matches!(data.response, QueryResponse::Full(_)));
+    }
+
+    #[test]
+    fn late_full_stat_after_fallback() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let mut data = [0; 1500];
+            let mut full_stat_session_id = vec![];
+            loop {
+                let (len, from) = socket.recv_from(&mut data).unwrap();
+                let mut response = data[2..7].to_vec(); // type and session id
+                if data[2] == HANDSHAKE_TYPE {
+                    response.extend_from_slice(b"9513307\0");
+                } else if len == 15 {
+                    // left unanswered until the client has fallen back to a basic stat
+                    full_stat_session_id = data[3..7].to_vec();
+                    continue;
+                } else {
+                    let late = [&[STAT_TYPE], full_stat_session_id.as_slice(), &full_stat(&[])].concat();
+                    socket.send_to(&late, from).unwrap();
+                    response.extend_from_slice(&basic_stat());
+                    socket.send_to(&response, from).unwrap();
+                    return;
+                }
+                socket.send_to(&response, from).unwrap();
+            }
+        });
+
+        let retry = RetryPolicy {
+            attempts: 1,
+            timeout: Duration::from_millis(200),
+        };
+        let pinger = QueryPinger {
+            local_addr: None,
+            handshake_retry: retry,
+            stat_retry: retry,
+            mode: QueryMode::FullOrBasic,
+        };
+        let data = Pinger::ping(&pinger, addr).unwrap();
+        server.join().unwrap();
+
+        let QueryResponse::Basic(stat) = data.response else {
+            panic!("expected a basic stat");
+        };
+        assert_eq!(stat.motd, "A Minecraft Server");
+        assert_eq!(stat.host_port, 25565);
+    }
+
+    #[test]
+    fn client_reuses_challenge_token() {
+        let addr = serve(3, false);
+        let client = QueryClient::new(pinger());
+
+        let first = client.ping(addr).unwrap();
+        assert_eq!((first.attempts.handshake, first.attempts.stat), (1, 1));
+        let second = client.ping(addr).unwrap();
+        assert_eq!((second.attempts.handshake, second.attempts.stat), (0, 1));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_query() {
+        let data = AsyncPinger::ping(&pinger(), serve(2, false)).await.unwrap();
+
+        let QueryResponse::Full(stat) = data.response else {
+            panic!("expected a full stat");
+        };
+        assert_eq!(stat.hostname, "A Minecraft Server");
+        assert_eq!((data.attempts.handshake, data.attempts.stat), (1, 1));
+    }
+}
@@ -1,8 +1,15 @@
+pub mod mc_bedrock;
 pub mod mc_legacy;
 pub mod mc_modern;
 pub mod mc_query;
+pub mod pool;
+pub mod tcp;
+mod udp;
 
-use std::net::SocketAddr;
+#[cfg(test)]
+mod recording;
+
+use std::{io::{Read, Write}, net::SocketAddr};
 
 pub trait Pinger {
 
@@ -13,4 +20,25 @@ pub trait Pinger {
     type Error: std::error::Error;
 
     fn ping(&self, addr: SocketAddr) -> std::result::Result<Self::Data, Self::Error>;
-}
\ No newline at end of file
+}
+
+/// A [`Pinger`] whose exchange can run over any established transport, such as an in-memory
+/// buffer or a proxied connection, rather than a connection it opens itself.
+pub trait StreamPinger: Pinger {
+
+    /// Pings the server at the other end of `stream`, which is listening on `port`.
+    fn ping_stream<S: Read + Write>(&self, stream: &mut S, port: u16) -> std::result::Result<Self::Data, Self::Error>;
+}
+
+/// Async counterpart of [`Pinger`], implemented with tokio.
+#[cfg(feature = "tokio")]
+pub trait AsyncPinger {
+
+    /// The data returned from a ping.
+    type Data;
+
+    /// A reported error value.
+    type Error: std::error::Error;
+
+    fn ping(&self, addr: SocketAddr) -> impl std::future::Future<Output = std::result::Result<Self::Data, Self::Error>> + Send;
+}
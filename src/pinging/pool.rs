@@ -0,0 +1,138 @@
+use std::{
+    net::SocketAddr,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use super::Pinger;
+
+/// Pings every address with at most `workers` pings in flight, handing each result to
+/// `on_result` on the calling thread as soon as it arrives.
+pub fn ping_concurrently<P>(
+    pinger: &P,
+    addresses: impl IntoIterator<Item = SocketAddr>,
+    workers: usize,
+    mut on_result: impl FnMut(SocketAddr, Result<P::Data, P::Error>),
+) where
+    P: Pinger + Sync,
+    P::Data: Send,
+    P::Error: Send,
+{
+    // popped from the back, so reverse to ping in the given order
+    let mut queue = addresses.into_iter().collect::<Vec<_>>();
+    queue.reverse();
+    let queue = Mutex::new(queue);
+    let workers = workers.clamp(1, queue.lock().unwrap().len().max(1));
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let queue = &queue;
+            s.spawn(move || loop {
+                let Some(addr) = queue.lock().unwrap().pop() else {
+                    break;
+                };
+                if tx.send((addr, pinger.ping(addr))).is_err() {
+                    break;
+                }
+            });
+        }
+        // the workers hold the remaining senders, so the loop ends once they all finish
+        drop(tx);
+
+        for (addr, result) in rx {
+            on_result(addr, result);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io,
+        thread::ThreadId,
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// Answers with the port of each address, failing for odd ports, and records the threads it
+    /// was called from.
+    #[derive(Default)]
+    struct FakePinger {
+        threads: Mutex<HashSet<ThreadId>>,
+    }
+
+    impl Pinger for FakePinger {
+        type Data = u16;
+
+        type Error = io::Error;
+
+        fn ping(&self, addr: SocketAddr) -> Result<Self::Data, Self::Error> {
+            self.threads.lock().unwrap().insert(thread::current().id());
+            thread::sleep(Duration::from_millis(5));
+
+            match addr.port() % 2 {
+                0 => Ok(addr.port()),
+                _ => Err(io::Error::other("odd port")),
+            }
+        }
+    }
+
+    fn addresses(count: u16) -> Vec<SocketAddr> {
+        (1..=count).map(|port| SocketAddr::from(([127, 0, 0, 1], port))).collect()
+    }
+
+    /// Pings `count` addresses, returning the results sorted by address and the number of
+    /// threads which pinged.
+    fn ping(count: u16, workers: usize) -> (Vec<(SocketAddr, io::Result<u16>)>, usize) {
+        let pinger = FakePinger::default();
+        let mut results = vec![];
+        ping_concurrently(&pinger, addresses(count), workers, |addr, res| results.push((addr, res)));
+
+        results.sort_by_key(|(addr, _)| *addr);
+        let threads = pinger.threads.lock().unwrap().len();
+        (results, threads)
+    }
+
+    #[test]
+    fn every_address_once() {
+        let (results, threads) = ping(20, 4);
+        let reported = results.iter().map(|(addr, _)| *addr).collect::<Vec<_>>();
+        assert_eq!(reported, addresses(20));
+        assert!(threads <= 4);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let (results, _) = ping(4, 2);
+        for (addr, res) in results {
+            match res {
+                Ok(port) => assert_eq!(port, addr.port()),
+                Err(e) => {
+                    assert_eq!(addr.port() % 2, 1);
+                    assert_eq!(e.to_string(), "odd port");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn workers_clamped() {
+        // no workers still pings, on a single thread
+        let (results, threads) = ping(5, 0);
+        assert_eq!(results.len(), 5);
+        assert_eq!(threads, 1);
+
+        // no more threads than addresses
+        let (results, threads) = ping(3, 64);
+        assert_eq!(results.len(), 3);
+        assert!(threads <= 3);
+
+        let (results, threads) = ping(0, 8);
+        assert!(results.is_empty());
+        assert_eq!(threads, 0);
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::{self, Cursor, Read, Write};
+
+/// A transport replaying the server's side of a recorded exchange, keeping what the client sends.
+pub struct Recording {
+    server: Cursor<Vec<u8>>,
+    pub client: Vec<u8>,
+}
+
+impl Recording {
+    pub fn new(server: Vec<u8>) -> Self {
+        Self {
+            server: Cursor::new(server),
+            client: vec![],
+        }
+    }
+}
+
+impl Read for Recording {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.server.read(buf)
+    }
+}
+
+impl Write for Recording {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.client.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
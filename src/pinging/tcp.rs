@@ -0,0 +1,173 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+/// Timeouts applied to the TCP connection of a ping.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub connect: Duration,
+    /// Maximum time a single read may block for.
+    pub read: Duration,
+    /// Maximum time a single write may block for.
+    pub write: Duration,
+    /// Maximum time the whole ping may take, from connecting to the last read.
+    pub total: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            read: Duration::from_secs(5),
+            write: Duration::from_secs(5),
+            total: Duration::from_secs(15),
+        }
+    }
+}
+
+/// A [`TcpStream`] which applies [`Timeouts`] to each operation, cutting them short once the
+/// overall deadline is reached.
+pub(crate) struct TimedStream {
+    stream: TcpStream,
+    timeouts: Timeouts,
+    deadline: Instant,
+}
+
+impl TimedStream {
+    pub fn connect(addr: SocketAddr, timeouts: Timeouts) -> io::Result<Self> {
+        let deadline = Instant::now() + timeouts.total;
+        let stream = TcpStream::connect_timeout(&addr, timeouts.connect.min(timeouts.total))?;
+
+        Ok(Self {
+            stream,
+            timeouts,
+            deadline,
+        })
+    }
+
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Caps `timeout` to the time left before the deadline.
+    fn remaining(&self, timeout: Duration) -> io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "ping deadline reached"));
+        }
+        Ok(timeout.min(remaining))
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = self.remaining(self.timeouts.read)?;
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TimedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let timeout = self.remaining(self.timeouts.write)?;
+        self.stream.set_write_timeout(Some(timeout))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Async counterpart of [`TimedStream`].
+#[cfg(feature = "tokio")]
+pub(crate) struct AsyncTimedStream {
+    stream: tokio::net::TcpStream,
+    timeouts: Timeouts,
+    deadline: Instant,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncTimedStream {
+    pub async fn connect(addr: SocketAddr, timeouts: Timeouts) -> io::Result<Self> {
+        let deadline = Instant::now() + timeouts.total;
+        let stream = with_timeout(
+            timeouts.connect.min(timeouts.total),
+            tokio::net::TcpStream::connect(addr),
+        )
+        .await?;
+
+        Ok(Self {
+            stream,
+            timeouts,
+            deadline,
+        })
+    }
+
+    pub fn get_ref(&self) -> &tokio::net::TcpStream {
+        &self.stream
+    }
+
+    fn remaining(&self, timeout: Duration) -> io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "ping deadline reached"));
+        }
+        Ok(timeout.min(remaining))
+    }
+
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let timeout = self.remaining(self.timeouts.read)?;
+        with_timeout(timeout, self.stream.read_exact(buf)).await?;
+        Ok(())
+    }
+
+    pub async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let timeout = self.remaining(self.timeouts.write)?;
+        with_timeout(timeout, self.stream.write_all(buf)).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(ErrorKind::TimedOut, "operation timed out")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn total_deadline() {
+        // connections are accepted by the kernel, but nothing is ever sent
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let timeouts = Timeouts {
+            total: Duration::from_millis(200),
+            ..Timeouts::default()
+        };
+
+        let start = Instant::now();
+        let mut stream = TimedStream::connect(listener.local_addr().unwrap(), timeouts).unwrap();
+        let mut buf = [0; 1];
+        assert!(stream.read(&mut buf).is_err());
+        assert!(start.elapsed() < timeouts.read, "read waited out its own timeout");
+
+        let e = stream.read(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        let e = stream.write(&[0]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+    }
+}
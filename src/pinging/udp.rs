@@ -0,0 +1,47 @@
+use std::{
+    io::{self, ErrorKind},
+    net::UdpSocket,
+    time::Instant,
+};
+
+/// Receives a single datagram, returning `None` if none arrives before `deadline`.
+pub(crate) fn recv_until(socket: &UdpSocket, buf: &mut [u8], deadline: Instant) -> io::Result<Option<usize>> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Ok(None);
+    }
+    socket.set_read_timeout(Some(remaining))?;
+
+    match socket.recv(buf) {
+        Ok(read) => Ok(Some(read)),
+        // unix reports a read timeout as `WouldBlock`, windows as `TimedOut`
+        Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn deadline() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        let mut buf = [0; 16];
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        assert_eq!(recv_until(&socket, &mut buf, deadline).unwrap(), None);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(recv_until(&socket, &mut buf, Instant::now()).unwrap(), None);
+
+        server.send_to(b"pong", socket.local_addr().unwrap()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(recv_until(&socket, &mut buf, deadline).unwrap(), Some(4));
+        assert_eq!(&buf[..4], b"pong");
+    }
+}
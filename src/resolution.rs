@@ -1,15 +1,31 @@
-use std::{collections::HashSet, io, net::SocketAddr};
+use std::{collections::HashSet, io, net::SocketAddr, time::{Duration, Instant}};
 
 use hickory_resolver::Resolver;
 
+/// Time spent on each step of resolving a Java Edition address.
+#[derive(Debug)]
+pub struct ResolutionTimings {
+    pub srv_lookup: Duration,
+    pub ip_lookup: Duration,
+}
+
 pub fn resolve_minecraft_ips(provided_address: (String, u16)) -> io::Result<HashSet<SocketAddr>> {
+    resolve_minecraft_ips_timed(provided_address).map(|(addresses, _)| addresses)
+}
+
+/// Like [`resolve_minecraft_ips`], also reporting how long each step took.
+pub fn resolve_minecraft_ips_timed(provided_address: (String, u16)) -> io::Result<(HashSet<SocketAddr>, ResolutionTimings)> {
     let resolver = Resolver::from_system_conf()?;
 
     let mut resolved_addresses: HashSet<SocketAddr> = HashSet::default();
 
 
+    let start = Instant::now();
+    let srv = resolver.srv_lookup(format!("_minecraft._tcp.{}", provided_address.0));
+    let srv_lookup = start.elapsed();
 
-    if let Ok(srv) = resolver.srv_lookup(format!("_minecraft._tcp.{}", provided_address.0))  {
+    let start = Instant::now();
+    if let Ok(srv) = srv  {
         
         log::debug!("got an SRV record: {:?}", srv);
         for v in srv {
@@ -20,13 +36,25 @@ pub fn resolve_minecraft_ips(provided_address: (String, u16)) -> io::Result<Hash
             }
         }
     } else {
-        let target = resolver.lookup_ip(&provided_address.0)?;
-
-        for address in target {
-            resolved_addresses.insert(SocketAddr::new(address, provided_address.1));
-        }
+        resolved_addresses = lookup_ips(&resolver, provided_address)?;
     }
+    let ip_lookup = start.elapsed();
+
+
+    Ok((resolved_addresses, ResolutionTimings { srv_lookup, ip_lookup }))
+}
+
+/// Resolves the address without looking for an SRV record, as is done for Bedrock servers.
+pub fn resolve_ips(provided_address: (String, u16)) -> io::Result<HashSet<SocketAddr>> {
+    let resolver = Resolver::from_system_conf()?;
+    lookup_ips(&resolver, provided_address)
+}
 
+fn lookup_ips(resolver: &Resolver, provided_address: (String, u16)) -> io::Result<HashSet<SocketAddr>> {
+    let target = resolver.lookup_ip(&provided_address.0)?;
 
-    Ok(resolved_addresses)
+    Ok(target
+        .into_iter()
+        .map(|address| SocketAddr::new(address, provided_address.1))
+        .collect())
 }